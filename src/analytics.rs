@@ -0,0 +1,214 @@
+// Model evaluation over chain records: reproducible splits, cross-validation and classification metrics
+
+use ndarray::{Array1, Array2, Axis};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::fmt;
+
+use super::logreg::{logistic_regression, predict, RegressionError, SolverOptions};
+
+// Probabilities are clipped to [EPS, 1 - EPS] before taking logs
+const EPS: f64 = 1e-15;
+
+//Counts of predicted against actual outcomes at a threshold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfusionMatrix {
+    pub true_pos: usize,
+    pub false_pos: usize,
+    pub true_neg: usize,
+    pub false_neg: usize
+}
+
+//Classification metrics of predicted probabilities at a single threshold
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    pub threshold: f64,
+    pub accuracy: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub roc_auc: f64,
+    pub log_loss: f64,
+    pub brier: f64,
+    pub confusion: ConfusionMatrix
+}
+
+//Metrics of one train/test split, one entry per requested threshold
+#[derive(Debug, Clone)]
+pub struct Evaluation {
+    pub train_size: usize,
+    pub test_size: usize,
+    pub metrics: Vec<Metrics>
+}
+
+//Shuffles the row indices with a seeded RNG and holds out test_fraction of them, returns (train, test)
+pub fn train_test_split(n: usize, test_fraction: f64, seed: u64) -> (Vec<usize>, Vec<usize>) {
+    let mut indices: Vec<usize> = (0..n).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    indices.shuffle(&mut rng);
+    let test_size = ((n as f64) * test_fraction.clamp(0.0, 1.0)).round() as usize;
+    let train = indices.split_off(test_size);
+    return (train, indices);
+}
+
+//Splits shuffled row indices into k folds, returns a (train, test) pair per fold
+pub fn k_fold(n: usize, k: usize, seed: u64) -> Vec<(Vec<usize>, Vec<usize>)> {
+    let mut indices: Vec<usize> = (0..n).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    indices.shuffle(&mut rng);
+    let mut folds = Vec::new();
+    for fold in 0..k {
+        let test: Vec<usize> = indices.iter().enumerate()
+            .filter(|(pos, _)| pos % k == fold)
+            .map(|(_, idx)| *idx)
+            .collect();
+        let train: Vec<usize> = indices.iter().enumerate()
+            .filter(|(pos, _)| pos % k != fold)
+            .map(|(_, idx)| *idx)
+            .collect();
+        folds.push((train, test));
+    }
+    return folds;
+}
+
+//Area under the ROC curve from the Mann-Whitney rank statistic, ties get their average rank
+pub fn roc_auc(y: &Array1<f64>, probs: &Array1<f64>) -> f64 {
+    let mut order: Vec<usize> = (0..probs.len()).collect();
+    order.sort_by(|&a, &b| probs[a].total_cmp(&probs[b]));
+
+    let mut ranks = vec![0.0; probs.len()];
+    let mut i = 0;
+    while i < order.len() {
+        let mut j = i;
+        while j + 1 < order.len() && probs[order[j + 1]] == probs[order[i]] {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        for k in i..=j {
+            ranks[order[k]] = rank;
+        }
+        i = j + 1;
+    }
+
+    let positives = y.iter().filter(|v| **v == 1.0).count() as f64;
+    let negatives = y.len() as f64 - positives;
+    if positives == 0.0 || negatives == 0.0 {
+        return f64::NAN;
+    }
+    let positive_rank_sum: f64 = y.iter().zip(ranks.iter())
+        .filter(|(v, _)| **v == 1.0)
+        .map(|(_, rank)| rank)
+        .sum();
+    return (positive_rank_sum - positives * (positives + 1.0) / 2.0) / (positives * negatives);
+}
+
+//Computes every metric of probs against the 0/1 outcomes y at threshold
+pub fn evaluate(y: &Array1<f64>, probs: &Array1<f64>, threshold: f64) -> Metrics {
+    let mut confusion = ConfusionMatrix { true_pos: 0, false_pos: 0, true_neg: 0, false_neg: 0 };
+    for (actual, prob) in y.iter().zip(probs.iter()) {
+        match (*prob >= threshold, *actual == 1.0) {
+            (true, true) => confusion.true_pos += 1,
+            (true, false) => confusion.false_pos += 1,
+            (false, false) => confusion.true_neg += 1,
+            (false, true) => confusion.false_neg += 1
+        }
+    }
+    let n = y.len() as f64;
+    let predicted_pos = (confusion.true_pos + confusion.false_pos) as f64;
+    let actual_pos = (confusion.true_pos + confusion.false_neg) as f64;
+    let log_loss = -y.iter().zip(probs.iter())
+        .map(|(actual, prob)| {
+            let p = prob.clamp(EPS, 1.0 - EPS);
+            actual * p.ln() + (1.0 - actual) * (1.0 - p).ln()
+        })
+        .sum::<f64>() / n;
+    let brier = y.iter().zip(probs.iter()).map(|(actual, prob)| (prob - actual).powi(2)).sum::<f64>() / n;
+    return Metrics {
+        threshold,
+        accuracy: (confusion.true_pos + confusion.true_neg) as f64 / n,
+        precision: if predicted_pos > 0.0 { confusion.true_pos as f64 / predicted_pos } else { 0.0 },
+        recall: if actual_pos > 0.0 { confusion.true_pos as f64 / actual_pos } else { 0.0 },
+        roc_auc: roc_auc(y, probs),
+        log_loss,
+        brier,
+        confusion
+    };
+}
+
+//Fits on the train rows and scores the test rows at each threshold
+pub fn evaluate_split(x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions,
+        train: &[usize], test: &[usize], thresholds: &[f64]) -> Result<Evaluation, RegressionError> {
+    let x_train = x.select(Axis(0), train);
+    let y_train = y.select(Axis(0), train);
+    let x_test = x.select(Axis(0), test);
+    let y_test = y.select(Axis(0), test);
+
+    let result = logistic_regression(&x_train, &y_train, names, options)?;
+    let probs = predict(&result.coefficients, &x_test);
+    let metrics = thresholds.iter().map(|t| evaluate(&y_test, &probs, *t)).collect();
+    return Ok(Evaluation { train_size: train.len(), test_size: test.len(), metrics });
+}
+
+//Single seeded hold-out evaluation
+pub fn holdout(x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions,
+        test_fraction: f64, seed: u64, thresholds: &[f64]) -> Result<Evaluation, RegressionError> {
+    let (train, test) = train_test_split(y.len(), test_fraction, seed);
+    return evaluate_split(x, y, names, options, &train, &test, thresholds);
+}
+
+//Seeded k-fold cross-validation, one evaluation per fold
+pub fn cross_validate(x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions,
+        k: usize, seed: u64, thresholds: &[f64]) -> Result<Vec<Evaluation>, RegressionError> {
+    if k < 2 || k > y.len() {
+        return Err(RegressionError::TooFewRows);
+    }
+    return k_fold(y.len(), k, seed).iter()
+        .map(|(train, test)| evaluate_split(x, y, names, options, train, test, thresholds))
+        .collect();
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "threshold {:.2}: accuracy {:.4}, precision {:.4}, recall {:.4}, roc auc {:.4}, log-loss {:.4}, brier {:.4}",
+            self.threshold, self.accuracy, self.precision, self.recall, self.roc_auc, self.log_loss, self.brier)?;
+        return write!(f, "  confusion: tp {} fp {} tn {} fn {}",
+            self.confusion.true_pos, self.confusion.false_pos, self.confusion.true_neg, self.confusion.false_neg);
+    }
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "train {} rows, test {} rows", self.train_size, self.test_size)?;
+        for metrics in &self.metrics {
+            writeln!(f, "{}", metrics)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn split_test() {
+        let (train, test) = train_test_split(10, 0.3, 7);
+        assert_eq!((train.len(), test.len()), (7, 3));
+        assert_eq!(train_test_split(10, 0.3, 7), (train.clone(), test.clone()));
+        let folds = k_fold(10, 3, 7);
+        let mut seen: Vec<usize> = folds.iter().flat_map(|(_, test)| test.clone()).collect();
+        seen.sort();
+        assert_eq!(seen, (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn metrics_test() {
+        let y = array![1.0, 1.0, 0.0, 0.0];
+        let probs = array![0.9, 0.4, 0.6, 0.1];
+        let metrics = evaluate(&y, &probs, 0.5);
+        assert_eq!(metrics.confusion, ConfusionMatrix { true_pos: 1, false_pos: 1, true_neg: 1, false_neg: 1 });
+        assert_eq!(metrics.accuracy, 0.5);
+        assert!((metrics.roc_auc - 0.75).abs() < 1e-12);
+        assert!((metrics.brier - (0.01 + 0.36 + 0.36 + 0.01) / 4.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,286 @@
+// Local HTTP/JSON interface to a chain, for tools that cannot link against the crate.
+// Every response body is JSON, errors are {"error": message}. Path segments and query values are percent-decoded.
+//
+//   GET  /head                        chain parameters, height and head block
+//   GET  /blocks/{id}                 block by height
+//   GET  /blocks/hash/{hash}          block by hash
+//   GET  /patients/{id}               every block holding the patient
+//   POST /patients                    mine a Patient sent as JSON and return the new block, or queue it when the server has a mempool
+//   GET  /mempool                     number of patients waiting to be mined
+//   GET  /validate                    validation report of the whole chain
+//   GET  /analytics/regression        ?outcome=icu&ridge=0.1 or &lasso=0.1, logistic regression on the default features
+
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::blockchain::{Blockchain, DuplicatePolicy, Patient};
+use super::logreg::{Penalty, SolverOptions};
+use super::mempool::{Mempool, MempoolError};
+use super::model::Outcome;
+
+//Status code and JSON body of a handled request
+pub type ApiResponse = (u16, Value);
+
+fn error(status: u16, message: impl ToString) -> ApiResponse {
+    return (status, json!({ "error": message.to_string() }));
+}
+
+//Answers one request against the chain. Submitted patients go to mempool when there is one instead of
+//being mined before the response. Split from the server so routes can be exercised without a socket
+pub fn route(blockchain: &Mutex<Blockchain>, mempool: Option<&Mempool>, method: &str, url: &str, body: &str) -> ApiResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let decoded: Vec<String> = match path.split('/').filter(|s| !s.is_empty()).map(|s| percent_decode(s, false)).collect() {
+        Some(decoded) => decoded,
+        None => return error(400, format!("invalid percent-encoding in {}", path))
+    };
+    let segments: Vec<&str> = decoded.iter().map(String::as_str).collect();
+    if let Some(pool) = mempool {
+        match (method, segments.as_slice()) {
+            ("POST", ["patients"]) => return match serde_json::from_str::<Patient>(body) {
+                Ok(patient) if rejects_repeat(&blockchain.lock().expect("chain lock poisoned"), &patient) => repeat_error(&patient),
+                Ok(patient) => match pool.submit(patient) {
+                    Ok(pending) => (202, json!({ "pending": pending })),
+                    Err(err @ MempoolError::Duplicate(_)) => error(409, err),
+                    Err(err @ MempoolError::Invalid(_)) => error(422, err),
+                    Err(err) => error(503, err)
+                },
+                Err(err) => error(400, format!("invalid patient: {}", err))
+            },
+            ("GET", ["mempool"]) => return (200, json!({ "pending": pool.len() })),
+            _ => {}
+        }
+    }
+    let mut chain = blockchain.lock().expect("chain lock poisoned");
+    return match (method, segments.as_slice()) {
+        ("GET", ["head"]) => (200, json!({ "params": chain.params, "height": chain.blocks.len(), "head": chain.blocks.last() })),
+        ("GET", ["blocks", "hash", hash]) => match chain.block_by_hash(hash) {
+            Some(block) => (200, json!(block)),
+            None => error(404, format!("no block with hash {}", hash))
+        },
+        ("GET", ["blocks", id]) => match id.parse::<u64>().ok().and_then(|id| chain.block(id)) {
+            Some(block) => (200, json!(block)),
+            None => error(404, format!("no block with id {}", id))
+        },
+        ("GET", ["patients", id]) => match chain.blocks_for(id) {
+            blocks if blocks.is_empty() => error(404, format!("no records for patient {}", id)),
+            blocks => (200, json!(blocks))
+        },
+        ("POST", ["patients"]) => match serde_json::from_str::<Patient>(body) {
+            Ok(patient) if rejects_repeat(&chain, &patient) => repeat_error(&patient),
+            Ok(patient) => {
+                let height = chain.blocks.len();
+                chain.add_patient_struct(patient);
+                match chain.blocks.get(height) {
+                    Some(block) => (201, json!(block)),
+                    None => error(422, "block was rejected by validation")
+                }
+            },
+            Err(err) => error(400, format!("invalid patient: {}", err))
+        },
+        ("GET", ["validate"]) => {
+            let report = chain.validate_chain_report(num_threads());
+            (200, json!({ "valid": report.is_valid(), "blocks_checked": report.blocks_checked, "errors": report.errors }))
+        },
+        ("GET", ["analytics", "regression"]) => match regression_options(query) {
+            Ok((outcome, options)) => match chain.run_regression(outcome, &options) {
+                Ok(result) => (200, json!(result)),
+                Err(err) => error(422, err)
+            },
+            Err(message) => error(400, message)
+        },
+        (_, ["head"] | ["blocks", ..] | ["patients", ..] | ["mempool"] | ["validate"] | ["analytics", "regression"]) => error(405, "method not allowed"),
+        _ => error(404, format!("no route for {}", path))
+    };
+}
+
+//True when the chain refuses a second record of this patient, checked before queueing or mining so the submitter
+//hears why instead of a bare validation failure
+fn rejects_repeat(chain: &Blockchain, patient: &Patient) -> bool {
+    return chain.params.committed_duplicate_policy() == DuplicatePolicy::Reject && chain.contains_id(&patient.id);
+}
+
+fn repeat_error(patient: &Patient) -> ApiResponse {
+    return error(409, format!("patient {} is already on the chain", patient.id));
+}
+
+//Decodes %XX escapes, and + as a space in query strings. None when an escape is malformed or the result is not UTF-8
+fn percent_decode(text: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'%' => {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+                continue;
+            },
+            b'+' if plus_as_space => bytes.push(b' '),
+            _ => bytes.push(byte)
+        }
+        rest = tail;
+    }
+    return String::from_utf8(bytes).ok();
+}
+
+//Outcome and penalty from a query string such as outcome=icu&ridge=0.1
+fn regression_options(query: &str) -> Result<(Outcome, SolverOptions), String> {
+    let mut outcome = Outcome::Died;
+    let mut options = SolverOptions::default();
+    for (key, value) in query.split('&').filter(|pair| !pair.is_empty()).filter_map(|pair| pair.split_once('=')) {
+        let (key, value) = match (percent_decode(key, true), percent_decode(value, true)) {
+            (Some(key), Some(value)) => (key, value),
+            _ => return Err(format!("invalid percent-encoding in {}", query))
+        };
+        let (key, value) = (key.as_str(), value.as_str());
+        let strength = || value.parse::<f64>().map_err(|_| format!("{} must be a number", key));
+        match key {
+            "outcome" => outcome = Outcome::from_name(value).ok_or(format!("unknown outcome {}", value))?,
+            "ridge" => options.penalty = Penalty::Ridge(strength()?),
+            "lasso" => options.penalty = Penalty::Lasso(strength()?),
+            _ => return Err(format!("unknown parameter {}", key))
+        }
+    }
+    return Ok((outcome, options));
+}
+
+fn num_threads() -> usize {
+    return std::thread::available_parallelism().map_or(1, |n| n.get());
+}
+
+//Blocking HTTP server answering requests one at a time on a shared chain
+pub struct ApiServer {
+    server: Server,
+    pub blockchain: Arc<Mutex<Blockchain>>,
+    //Where submitted patients are queued for a BlockProducer, they are mined during the request when None
+    pub mempool: Option<Arc<Mempool>>,
+    //Chain file rewritten after every patient mined during a request, nothing is saved when None
+    pub save_path: Option<String>
+}
+
+impl ApiServer {
+    //Listens on addr, e.g. 127.0.0.1:8080. Port 0 picks a free port, see local_addr
+    pub fn bind(addr: &str, blockchain: Arc<Mutex<Blockchain>>) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        return Ok(Self { server: Server::http(addr)?, blockchain, mempool: None, save_path: None });
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        return self.server.server_addr().to_ip();
+    }
+
+    //Serves requests until the listener fails
+    pub fn run(&self) {
+        for request in self.server.incoming_requests() {
+            if let Err(err) = self.respond(request) {
+                log::error!("failed to answer request: {}", err);
+            }
+        }
+    }
+
+    //Waits for and answers a single request
+    pub fn handle_one(&self) -> io::Result<()> {
+        return self.respond(self.server.recv()?);
+    }
+
+    fn respond(&self, mut request: Request) -> io::Result<()> {
+        let mut body = String::new();
+        request.as_reader().read_to_string(&mut body)?;
+        let method = match request.method() {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            _ => "OTHER"
+        };
+        let (status, value) = route(&self.blockchain, self.mempool.as_deref(), method, request.url(), &body);
+        if status == 201 {
+            if let Some(path) = &self.save_path {
+                if let Err(err) = self.blockchain.lock().expect("chain lock poisoned").save(path) {
+                    log::error!("failed to save chain to {}: {}", path, err);
+                }
+            }
+        }
+        let header = Header::from_bytes("Content-Type", "application/json").expect("static header is valid");
+        return request.respond(Response::from_string(value.to_string()).with_status_code(status).with_header(header));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+
+    fn patient_json(id: &str) -> String {
+        return json!({
+            "id": id, "sex": "Female", "patient_type": "Inpatient", "entry_date": "2020-05-10", "date_symptoms": "2020-05-08",
+            "date_died": null, "intubed": "No", "pneumonia": "No", "age": 40, "pregnancy": "No", "diabetes": "No", "copd": "No",
+            "asthma": "No", "inmsupr": "No", "hypertension": "No", "other_disease": "No", "cardiovascular": "No", "obesity": "No",
+            "renal_chronic": "No", "tobacco": "No", "contact_other_covid": "Unknown", "covid_res": "Positive", "icu": "No"
+        }).to_string();
+    }
+
+    #[test]
+    fn route_test() {
+        let chain = Mutex::new(Blockchain::new());
+        assert_eq!(route(&chain, None, "GET", "/head", "").1["height"], 0);
+        assert_eq!(route(&chain, None, "POST", "/patients", &patient_json("a")).0, 201);
+        assert_eq!(route(&chain, None, "POST", "/patients", &patient_json("a")), error(409, "patient a is already on the chain"));
+        let (status, block) = route(&chain, None, "POST", "/patients", &patient_json("b"));
+        assert_eq!(status, 201);
+        assert_eq!(block["id"], 1);
+
+        let hash = block["hash"].as_str().unwrap();
+        assert_eq!(route(&chain, None, "GET", &format!("/blocks/hash/{}", hash), "").1["id"], 1);
+        assert_eq!(route(&chain, None, "GET", "/blocks/0", "").1["payload"]["id"], "a");
+        assert_eq!(route(&chain, None, "GET", "/patients/b", "").1[0]["id"], 1);
+        assert_eq!(route(&chain, None, "GET", "/patients/c", "").0, 404);
+        assert_eq!(route(&chain, None, "POST", "/patients", &patient_json("d e")).0, 201);
+        assert_eq!(route(&chain, None, "GET", "/patients/d%20e", "").1[0]["payload"]["id"], "d e");
+        assert_eq!(route(&chain, None, "GET", "/patients/d%2", "").0, 400);
+        assert_eq!(route(&chain, None, "GET", "/analytics/regression?outcome=%69cu&ridge=1e%2D1", ""),
+            route(&chain, None, "GET", "/analytics/regression?outcome=icu&ridge=1e-1", ""));
+        assert_eq!(route(&chain, None, "POST", "/patients", "{}").0, 400);
+        assert_eq!(route(&chain, None, "POST", "/patients", &patient_json("c").replace("\"sex\":\"Female\"", "\"sex\":\"Unknown\"")).0, 400);
+        assert_eq!(route(&chain, None, "POST", "/patients", &patient_json("")).0, 422);
+        assert_eq!(route(&chain, None, "GET", "/validate", "").1["valid"], true);
+        assert_eq!(route(&chain, None, "GET", "/analytics/regression?outcome=nope", "").0, 400);
+        assert_eq!(route(&chain, None, "DELETE", "/head", "").0, 405);
+    }
+
+    #[test]
+    fn mempool_route_test() {
+        let chain = Mutex::new(Blockchain::new());
+        let pool = Mempool::new(10);
+        assert_eq!(route(&chain, Some(&pool), "POST", "/patients", &patient_json("a")).0, 202);
+        assert_eq!(route(&chain, Some(&pool), "POST", "/patients", &patient_json("a")).0, 409);
+        assert_eq!(route(&chain, Some(&pool), "GET", "/mempool", "").1["pending"], 1);
+        assert_eq!(route(&chain, Some(&pool), "GET", "/head", "").1["height"], 0);
+        chain.lock().unwrap().add_patient_struct(pool.take_batch(1).unwrap().remove(0));
+        assert_eq!(route(&chain, Some(&pool), "POST", "/patients", &patient_json("a")).0, 409);
+    }
+
+    #[test]
+    fn localhost_test() {
+        let server = ApiServer::bind("127.0.0.1:0", Arc::new(Mutex::new(Blockchain::new()))).unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            server.handle_one().unwrap();
+            return server;
+        });
+
+        let body = patient_json("a");
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /patients HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", addr, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201"));
+        assert!(response.contains("\"previous_hash\""));
+
+        let server = handle.join().unwrap();
+        assert_eq!(server.blockchain.lock().unwrap().blocks.len(), 1);
+    }
+}
@@ -1 +0,0 @@
-pub mod lib;
\ No newline at end of file
@@ -1,2 +0,0 @@
-pub mod blockchain;
-pub mod logreg;
\ No newline at end of file
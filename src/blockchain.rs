@@ -0,0 +1,1518 @@
+use csv::StringRecord;
+
+use log::{error, warn};
+use chrono::{NaiveDate, Utc};
+use sha2::{Sha256, Digest};
+use rand::prelude::*;
+use serde::{Serialize, Deserialize, Deserializer};
+use serde::de::{self, DeserializeOwned};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+
+use ndarray::{Array1, Array2};
+use super::logreg::{logistic_regression, penalty_grid, regularization_path, RegressionError, RegressionResult, SolverOptions};
+
+use super::analytics::{cross_validate, holdout, Evaluation, Metrics};
+use super::export::{ExportError, ExportOptions};
+use super::fhir::{read_bundle, MappingReport};
+use super::classifier::{compare_classifiers, Classifier};
+use super::federated::{federated_regression, HospitalNode};
+use super::privacy::{PrivacyBudget, PrivacyError};
+use super::survival::{cox_regression, kaplan_meier_by_group, CoxResult, KaplanMeierCurve, SurvivalRecord, TimeOrigin};
+use std::collections::{BTreeMap, HashMap};
+use super::model::{design_matrix, feature_names, training_data, Feature, LogisticModel, ModelAnchor, Outcome, DEFAULT_FEATURES};
+
+use super::online::OnlineLearner;
+pub use super::patient::{Patient, PatientError};
+
+use std::sync::{mpsc, mpsc::Receiver, mpsc::Sender};
+use std::thread;
+use std::thread::JoinHandle;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+const DIFFICULTY_PREFIX: &str = "000";
+const NUM_OF_ROWS_COVID: u64 = 566602;
+const VALIDATION_THREADS: usize = 8;
+//Privacy budget of a new chain, until set_privacy_budget changes it
+pub const DEFAULT_PRIVACY_EPSILON: f64 = 1.0;
+pub const DEFAULT_PRIVACY_DELTA: f64 = 1e-6;
+
+//Schema version of patients saved with the dataset's codes, before records had typed fields
+pub const LEGACY_SCHEMA_VERSION: u32 = 0;
+//Schema version of the first typed encoding of a payload
+pub const FIRST_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+//Enum used to validate block
+pub enum BlockError {
+    InvalidPreviousHash,
+    InvalidPayload,
+    InvalidID,
+    IncorrectHash,
+    InvalidGenesis,
+    InsufficientDifficulty,
+    TimestampTooEarly,
+    TimestampInFuture,
+    DuplicateRecord,
+    AnchorOffChain
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            BlockError::InvalidPreviousHash => write!(f, "has wrong previous hash"),
+            BlockError::InvalidPayload => write!(f, "has a payload that fails its checks"),
+            BlockError::IncorrectHash => write!(f, "has wrong incorrect hash"),
+            BlockError::InvalidID => write!(f, "has wrong invalid ID"),
+            BlockError::InvalidGenesis => write!(f, "is not a valid genesis block for these chain parameters"),
+            BlockError::InsufficientDifficulty => write!(f, "has a hash that does not meet the chain difficulty"),
+            BlockError::TimestampTooEarly => write!(f, "has a timestamp earlier than the chain allows"),
+            BlockError::TimestampInFuture => write!(f, "has a timestamp too far in the future"),
+            BlockError::DuplicateRecord => write!(f, "repeats a record id already on the chain"),
+            BlockError::AnchorOffChain => write!(f, "commits to a block that is not on the chain")
+        };
+    }
+}
+
+//Result of checking every block and model anchor on the chain
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    pub blocks_checked: usize,
+    //Every invalid block id with the first rule it broke, in chain order
+    pub errors: Vec<(u64, BlockError)>,
+    pub anchors_checked: usize,
+    //Position of every invalid model anchor with the first rule it broke
+    pub anchor_errors: Vec<(usize, BlockError)>
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        return self.errors.is_empty() && self.anchor_errors.is_empty();
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "checked {} blocks, {} invalid", self.blocks_checked, self.errors.len())?;
+        for (id, err) in &self.errors {
+            writeln!(f, "block with id: {} {}", id, err)?;
+        }
+        if self.anchors_checked > 0 {
+            writeln!(f, "checked {} model anchors, {} invalid", self.anchors_checked, self.anchor_errors.len())?;
+        }
+        for (index, err) in &self.anchor_errors {
+            writeln!(f, "model anchor {} {}", index, err)?;
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//How blocks are agreed on
+pub enum Consensus {
+    ProofOfWork
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//Lower bound on a block's timestamp given the blocks before it
+pub enum TimestampRule {
+    //Not earlier than the previous block
+    Monotonic,
+    //Not earlier than the median of the previous n blocks, tolerating a single clock running behind
+    MedianPast(usize)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//What happens when a record id already on the chain is added again
+pub enum DuplicatePolicy {
+    //The record is refused and a block repeating an id is invalid
+    Reject,
+    //The record is added and supersedes the earlier ones, see latest_record
+    Amend,
+    //The record is added and a warning is logged
+    AllowWithWarning
+}
+
+//Field set of the chain parameters new chains hash. Version 1 has the chain id, difficulty, consensus and
+//schema version, 2 adds the timestamp rule and future drift and 3 the duplicate policy
+pub const PARAMS_VERSION: u32 = 3;
+
+//How chains created before the duplicate policy existed treat a repeated id, they never refused one
+const UNCOMMITTED_DUPLICATE_POLICY: DuplicatePolicy = DuplicatePolicy::AllowWithWarning;
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+//Parameters identifying a network. Their hash is the genesis block's previous hash, so every block
+//hash commits to them and chains from two networks cannot be mistaken for one another
+pub struct ChainParams {
+    pub chain_id: String,
+    //Number of leading zero hex digits a block hash needs
+    pub difficulty: usize,
+    pub consensus: Consensus,
+    pub schema_version: u32,
+    pub timestamp_rule: TimestampRule,
+    //Seconds a block's timestamp may be ahead of the validating node's clock
+    pub max_future_drift: i64,
+    pub duplicate_policy: DuplicatePolicy,
+    //Field set the hash commits to, that of the version the chain was created with
+    pub params_version: u32
+}
+
+#[derive(Deserialize)]
+//Parameters as saved. Chains created before a field existed lack it, and their hash leaves it out
+struct StoredParams {
+    chain_id: String,
+    difficulty: usize,
+    consensus: Consensus,
+    schema_version: u32,
+    timestamp_rule: Option<TimestampRule>,
+    max_future_drift: Option<i64>,
+    duplicate_policy: Option<DuplicatePolicy>,
+    params_version: Option<u32>
+}
+
+impl<'de> Deserialize<'de> for ChainParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredParams::deserialize(deserializer)?;
+        let params_version = stored.params_version.unwrap_or(match (stored.timestamp_rule, stored.duplicate_policy) {
+            (_, Some(_)) => 3,
+            (Some(_), None) => 2,
+            (None, None) => 1
+        });
+        let defaults = ChainParams::default();
+        return Ok(ChainParams {
+            chain_id: stored.chain_id,
+            difficulty: stored.difficulty,
+            consensus: stored.consensus,
+            schema_version: stored.schema_version,
+            timestamp_rule: stored.timestamp_rule.unwrap_or(defaults.timestamp_rule),
+            max_future_drift: stored.max_future_drift.unwrap_or(defaults.max_future_drift),
+            duplicate_policy: stored.duplicate_policy.unwrap_or(UNCOMMITTED_DUPLICATE_POLICY),
+            params_version
+        });
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        return Self { chain_id: String::from("carlechain"), difficulty: DIFFICULTY_PREFIX.len(), consensus: Consensus::ProofOfWork, schema_version: 1,
+            timestamp_rule: TimestampRule::MedianPast(11), max_future_drift: 2 * 60 * 60, duplicate_policy: DuplicatePolicy::Reject,
+            params_version: PARAMS_VERSION };
+    }
+}
+
+impl ChainParams {
+    //SHA256 of the JSON encoding of the fields in the chain's params_version, in declaration order
+    pub fn hash(&self) -> String {
+        let mut fields: Vec<(&str, Value)> = vec![("chain_id", Value::from(self.chain_id.as_str())), ("difficulty", Value::from(self.difficulty)),
+            ("consensus", serde_json::to_value(self.consensus).expect("consensus serializes")), ("schema_version", Value::from(self.schema_version))];
+        if self.params_version >= 2 {
+            fields.push(("timestamp_rule", serde_json::to_value(self.timestamp_rule).expect("timestamp rule serializes")));
+            fields.push(("max_future_drift", Value::from(self.max_future_drift)));
+        }
+        if self.params_version >= 3 {
+            fields.push(("duplicate_policy", serde_json::to_value(self.duplicate_policy).expect("duplicate policy serializes")));
+        }
+        let encoded: Vec<String> = fields.iter().map(|(name, value)| format!("{}:{}", Value::from(*name), value)).collect();
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{{{}}}", encoded.join(",")).as_bytes());
+        return hex::encode(hasher.finalize());
+    }
+
+    //Duplicate policy the genesis block commits to, the same on every node. Chains of params versions before 3
+    //did not hash one and keep allowing repeats whatever policy their file names
+    pub fn committed_duplicate_policy(&self) -> DuplicatePolicy {
+        return if self.params_version >= 3 { self.duplicate_policy } else { UNCOMMITTED_DUPLICATE_POLICY };
+    }
+
+    pub fn difficulty_prefix(&self) -> String {
+        return "0".repeat(self.difficulty);
+    }
+
+    //Earliest timestamp allowed for the block following ancestors
+    pub fn earliest_timestamp<T>(&self, ancestors: &[Block<T>]) -> Option<i64> {
+        let span = match self.timestamp_rule {
+            TimestampRule::Monotonic => 1,
+            TimestampRule::MedianPast(n) => n.max(1)
+        };
+        let mut past: Vec<i64> = ancestors.iter().rev().take(span).map(|block| block.timestamp).collect();
+        past.sort_unstable();
+        return past.get(past.len() / 2).copied();
+    }
+}
+
+//Record a block can carry. Patients are the default, other clinical events such as lab results or
+//consent records implement it to be stored on the same chain machinery
+pub trait Payload: Serialize + DeserializeOwned + Clone + fmt::Debug + Send + Sync + 'static {
+    type Error: fmt::Display;
+
+    //Identifier the chain indexes records by and applies its DuplicatePolicy to
+    fn id(&self) -> &str;
+
+    //Version of the JSON encoding new records are mined with. Raise it whenever the type's fields change
+    //and teach upgrade to read the previous encodings
+    const SCHEMA_VERSION: u32 = FIRST_SCHEMA_VERSION;
+
+    //Rules the record must meet to be mined and for its block to validate
+    fn check(&self) -> Result<(), Self::Error>;
+
+    //Reads a record stored under an earlier schema version, the current one is read by serde
+    fn upgrade(version: u32, _encoded: Value) -> Result<Self, String> {
+        return Err(format!("no deserializer for schema version {}", version));
+    }
+}
+
+//Reads a stored payload with the deserializer of the schema version it was written under
+pub fn decode_payload<T: Payload>(version: u32, encoded: Value) -> Result<T, String> {
+    if version == T::SCHEMA_VERSION {
+        return serde_json::from_value(encoded).map_err(|err| err.to_string());
+    }
+    return T::upgrade(version, encoded);
+}
+
+//SHA256 of a payload's JSON encoding, with object keys in sorted order
+pub fn payload_digest(encoded: &Value) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(encoded.to_string().as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//Schema version of a payload saved before blocks recorded one: the first typed encoding when it reads as one,
+//the legacy encoding otherwise
+fn unversioned_schema<T: Payload>(encoded: &Value) -> u32 {
+    if decode_payload::<T>(FIRST_SCHEMA_VERSION, encoded.clone()).is_ok() {
+        return FIRST_SCHEMA_VERSION;
+    }
+    return LEGACY_SCHEMA_VERSION;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//What a block hash commits to besides the block header. Each block keeps the rule it was mined under
+pub enum HashRule {
+    //The payload id only, blocks saved before payloads were hashed
+    PayloadId,
+    //SHA256 of the payload serialized in field order, blocks saved before they carried a schema version
+    FieldOrderDigest,
+    //The schema version and the payload_digest of the encoded payload
+    VersionedDigest
+}
+
+impl HashRule {
+    //Rule new blocks are mined under, left out when a block is saved
+    pub fn is_current(&self) -> bool {
+        return *self == HashRule::VersionedDigest;
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+//Single block structure
+pub struct Block<T = Patient> {
+    pub id: u64,
+    pub hash: String,
+    pub previous_hash: String,
+    pub timestamp: i64,
+    pub nonce: u64,
+    //Schema version of the encoding the hash commits to
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "HashRule::is_current")]
+    pub hash_rule: HashRule,
+    //Record read under the current schema
+    pub payload: T,
+    //Hospital whose node mined the block, None for blocks of nodes that do not name one and blocks mined before
+    //blocks could. The hash commits to it when present
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hospital: Option<String>,
+    //Encoding the block was mined with when that is an older schema than the payload's current one.
+    //It is saved alongside the migrated payload so the original hash still verifies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<Value>
+}
+
+#[derive(Deserialize)]
+//Block as saved, before its payload is read under the schema it was written with
+struct StoredBlock {
+    id: u64,
+    hash: String,
+    previous_hash: String,
+    timestamp: i64,
+    nonce: u64,
+    //Missing from blocks saved before schema versions, whose encoding is read off the payload
+    schema_version: Option<u32>,
+    hash_rule: Option<HashRule>,
+    payload: Option<Value>,
+    //Chains saved before payloads were generic call the payload patient_info, and hashed its id only
+    patient_info: Option<Value>,
+    hospital: Option<String>,
+    original: Option<Value>
+}
+
+impl<'de, T: Payload> Deserialize<'de> for Block<T> {
+    //The payload is always re-read from the encoding the hash commits to, so a payload migrated by an
+    //older version of the schema is migrated again under the current one
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredBlock::deserialize(deserializer)?;
+        let id_only = stored.patient_info.is_some();
+        let encoded = stored.original.or(stored.payload).or(stored.patient_info)
+            .ok_or_else(|| de::Error::custom(format!("block {}: missing payload", stored.id)))?;
+        let schema_version = stored.schema_version.unwrap_or_else(|| unversioned_schema::<T>(&encoded));
+        let hash_rule = match (stored.hash_rule, stored.schema_version) {
+            (Some(rule), _) => rule,
+            (None, Some(LEGACY_SCHEMA_VERSION)) => HashRule::PayloadId,
+            (None, Some(_)) => HashRule::VersionedDigest,
+            (None, None) if id_only => HashRule::PayloadId,
+            (None, None) => HashRule::FieldOrderDigest
+        };
+        let payload = decode_payload::<T>(schema_version, encoded.clone())
+            .map_err(|err| de::Error::custom(format!("block {}: {}", stored.id, err)))?;
+        let original = if schema_version == T::SCHEMA_VERSION { None } else { Some(encoded) };
+        return Ok(Block { id: stored.id, hash: stored.hash, previous_hash: stored.previous_hash, timestamp: stored.timestamp,
+            nonce: stored.nonce, schema_version, hash_rule, payload, hospital: stored.hospital, original });
+    }
+}
+
+impl<T: Payload> Block<T> {
+    //Encoding of the payload the hash commits to
+    pub fn encoded_payload(&self) -> Value {
+        return match &self.original {
+            Some(encoded) => encoded.clone(),
+            None => serde_json::to_value(&self.payload).expect("payload serializes")
+        };
+    }
+
+    //Hash the block should have, under the hashing rule it was mined with
+    pub fn compute_hash(&self) -> String {
+        return match self.hash_rule {
+            HashRule::PayloadId => generate_legacy_hash(self.id, self.previous_hash.clone(), self.timestamp, self.nonce, self.payload.id().to_string()),
+            //Field order is that of the payload type, which only reproduces the digest while the type is at its first schema version
+            HashRule::FieldOrderDigest => generate_unversioned_hash(self.id, self.previous_hash.clone(), self.timestamp, self.nonce,
+                field_order_digest(&self.payload)),
+            HashRule::VersionedDigest => generate_hash(self.id, self.previous_hash.clone(), self.timestamp, self.nonce, self.schema_version,
+                payload_digest(&self.encoded_payload()), self.hospital.as_deref())
+        };
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound(deserialize = "T: Payload"))]
+//blocks represents entire ledger, model_anchors commits fitted models to the blocks they were trained on
+//and privacy_budget is charged by every differentially private query on the ledger
+pub struct Blockchain<T = Patient> {
+    pub params: ChainParams,
+    pub blocks: Vec<Block<T>>,
+    pub model_anchors: Vec<ModelAnchor>,
+    pub privacy_budget: PrivacyBudget,
+    //Hospital recorded on the blocks this node mines. A setting of the node, not saved with the chain
+    #[serde(skip)]
+    pub hospital: Option<String>,
+    #[serde(skip)]
+    subscribers: Vec<Sender<Block<T>>>,
+    //Block ids holding each record id, rebuilt when a chain is loaded
+    #[serde(skip)]
+    record_index: HashMap<String, Vec<u64>>
+}
+
+//Reader threads of a CSV and the channel each sends its parsed patients on
+pub type ChunkReaders = (Vec<JoinHandle<()>>, Receiver<Vec<Patient>>);
+
+//Reads string given a vector of iterators to lines in the CSV
+pub fn string_reader(records: &[StringRecord], pb: &ProgressBar, m: &MultiProgress) -> Vec<Patient> {
+    let mut patients: Vec<Patient> = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        match Patient::from_record(record) {
+            Ok(patient) => patients.push(patient),
+            Err(err) => error!("skipping patient record {}: {}", record.get(0).unwrap_or(""), err)
+        }
+        pb.set_message(format!("item #{}", i + 1));
+        pb.inc(1);
+    }
+    m.println("").unwrap();
+    pb.finish_with_message("done");
+    return patients;
+}
+
+impl<T: Payload> Default for Blockchain<T> {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl<T: Payload> Blockchain<T> {
+    pub fn new() -> Self {
+        return Self::with_params(ChainParams::default());
+    }
+
+    //Empty chain for the network described by params
+    pub fn with_params(params: ChainParams) -> Self {
+        return Self { params, blocks: vec![], model_anchors: vec![], privacy_budget: PrivacyBudget::new(DEFAULT_PRIVACY_EPSILON, DEFAULT_PRIVACY_DELTA),
+            hospital: None, subscribers: vec![], record_index: HashMap::new() };
+    }
+
+    //Creates the first block in the blockchain, mined on top of the chain parameters' hash
+    fn genesis(&mut self, payload: T) {
+        let id = 0;
+        let timestamp = Utc::now().timestamp();
+        let previous_hash = self.params.hash();
+        let digest = payload_digest(&serde_json::to_value(&payload).expect("payload serializes"));
+        let (nonce, hash) = mine_block(id, timestamp, &previous_hash, T::SCHEMA_VERSION, digest, self.hospital.as_deref(),
+            &self.params.difficulty_prefix());
+
+        let genesis_block = Block {
+            id,
+            timestamp,
+            previous_hash,
+            schema_version: T::SCHEMA_VERSION,
+            hash_rule: HashRule::VersionedDigest,
+            payload,
+            hospital: self.hospital.clone(),
+            original: None,
+            nonce,
+            hash
+        };
+        self.push_block(genesis_block);
+    }
+
+    //Returns a channel receiving a copy of every block appended from now on
+    pub fn subscribe(&mut self) -> Receiver<Block<T>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.push(tx);
+        return rx;
+    }
+
+    //Appends a block and publishes it to subscribers, forgetting those that hung up
+    fn push_block(&mut self, block: Block<T>) {
+        self.subscribers.retain(|tx| tx.send(block.clone()).is_ok());
+        self.record_index.entry(block.payload.id().to_string()).or_default().push(block.id);
+        self.blocks.push(block);
+    }
+
+    fn rebuild_record_index(&mut self) {
+        self.record_index.clear();
+        for block in &self.blocks {
+            self.record_index.entry(block.payload.id().to_string()).or_default().push(block.id);
+        }
+    }
+
+    //True when a block already holds a record with this id
+    pub fn contains_id(&self, id: &str) -> bool {
+        return self.record_index.contains_key(id);
+    }
+
+    //Checks a record against its own rules and the chain's duplicate policy, then mines it onto the chain
+    pub fn add_record(&mut self, payload: T) {
+        if let Err(err) = payload.check() {
+            error!("record {} rejected: {}", payload.id(), err);
+            return;
+        }
+        if self.contains_id(payload.id()) {
+            match self.params.committed_duplicate_policy() {
+                DuplicatePolicy::Reject => {
+                    error!("record {} is already on the chain, rejected", payload.id());
+                    return;
+                },
+                DuplicatePolicy::AllowWithWarning => warn!("record {} is already on the chain, adding another", payload.id()),
+                DuplicatePolicy::Amend => {}
+            }
+        }
+        if self.blocks.is_empty() {
+            self.genesis(payload);
+        }
+        else {
+            self.add_record_nonempty(payload);
+        }
+    }
+
+    //Adds a record when the blockchain is not empty
+    fn add_record_nonempty(&mut self, payload: T) {
+        let block: Block<T> = self.create_block(payload);
+        self.try_add_block(block);
+    }
+
+    //Creates a block and ensures the block is mined
+    fn create_block(&self, payload: T) -> Block<T> {
+        let id = self.blocks.last().expect("Blockchain is not empty").id + 1;
+        let timestamp = Utc::now().timestamp();
+        let previous_hash = &self.blocks.last().expect("Blockchain is not empty").hash;
+        let digest = payload_digest(&serde_json::to_value(&payload).expect("payload serializes"));
+        let (nonce, hash) = mine_block(id, timestamp, previous_hash.as_str(), T::SCHEMA_VERSION, digest, self.hospital.as_deref(),
+            &self.params.difficulty_prefix());
+        return Block {id, hash, previous_hash : previous_hash.clone(), timestamp, nonce, schema_version: T::SCHEMA_VERSION,
+            hash_rule: HashRule::VersionedDigest, payload, hospital: self.hospital.clone(), original: None};
+    }
+
+    //Adds a block given there is no issue with validation against the current chain
+    fn try_add_block(&mut self, block: Block<T>) {
+        let res: Result<bool, BlockError> = self.validate_block(&block, &self.blocks, Utc::now().timestamp());
+        match res {
+            Ok(_) => self.push_block(block),
+            Err(err) => error!("block with id: {} {}", block.id, err)
+        }
+    }
+
+    //Validates a block against the blocks before it by checking id, previous hash, timestamp, payload, and current hash
+    fn validate_block(&self, block: &Block<T>, ancestors: &[Block<T>], now: i64) -> Result<bool, BlockError> {
+        let curr_last_block = ancestors.last().expect("block has a predecessor");
+        if curr_last_block.hash != block.previous_hash {
+            return Err(BlockError::InvalidPreviousHash);
+        } else if block.id != curr_last_block.id + 1 {
+            return Err(BlockError::InvalidID);
+        } else if self.params.earliest_timestamp(ancestors).is_some_and(|earliest| block.timestamp < earliest) {
+            return Err(BlockError::TimestampTooEarly);
+        }
+        return self.validate_contents(block, now);
+    }
+
+    //Validates the genesis block like any other, with the chain parameters' hash standing in for a predecessor
+    fn validate_genesis(&self, block: &Block<T>, now: i64) -> Result<bool, BlockError> {
+        if block.id != 0 || block.previous_hash != self.params.hash() {
+            return Err(BlockError::InvalidGenesis);
+        }
+        return self.validate_contents(block, now);
+    }
+
+    //Checks the stored hash against the block's data and the chain difficulty, that its payload passes its checks,
+    //and that it was not stamped too far past the validator's clock
+    fn validate_contents(&self, block: &Block<T>, now: i64) -> Result<bool, BlockError> {
+        if block.timestamp > now + self.params.max_future_drift {
+            return Err(BlockError::TimestampInFuture);
+        } else if block.compute_hash() != block.hash {
+            return Err(BlockError::IncorrectHash);
+        } else if !block.hash.starts_with(&self.params.difficulty_prefix()) {
+            return Err(BlockError::InsufficientDifficulty);
+        } else if block.payload.check().is_err() {
+            return Err(BlockError::InvalidPayload);
+        }
+        return Ok(true);
+    }
+
+    //Validates each block on the chain
+    pub fn validate_chain(&self) -> bool {
+        return self.validate_chain_report(VALIDATION_THREADS).is_valid();
+    }
+
+    //Validates every block across num_threads threads. Each block only depends on its stored
+    //previous_hash and its predecessor, so the chain is split into contiguous ranges checked independently
+    pub fn validate_chain_report(&self, num_threads: usize) -> ValidationReport {
+        let mut errors: Vec<(u64, BlockError)> = Vec::new();
+        let now = Utc::now().timestamp();
+        if let Some(first) = self.blocks.first() {
+            if let Err(err) = self.validate_genesis(first, now) {
+                errors.push((first.id, err));
+            }
+        }
+        //Height each record id first appears at, only needed when repeats are invalid
+        let mut first_seen: HashMap<&str, u64> = HashMap::new();
+        if self.params.committed_duplicate_policy() == DuplicatePolicy::Reject {
+            for block in &self.blocks {
+                first_seen.entry(block.payload.id()).or_insert(block.id);
+            }
+        }
+        let first_seen = &first_seen;
+        let len = self.blocks.len();
+        let chunk = len.saturating_sub(1).div_ceil(num_threads.max(1)).max(1);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (1..len).step_by(chunk)
+                .map(|start| scope.spawn(move || {
+                    return (start..(start + chunk).min(len))
+                        .filter_map(|i| {
+                            let block = &self.blocks[i];
+                            let repeated = first_seen.get(block.payload.id()).is_some_and(|first| *first != block.id);
+                            return self.validate_block(block, &self.blocks[..i], now)
+                                .and_then(|valid| if repeated { Err(BlockError::DuplicateRecord) } else { Ok(valid) })
+                                .err()
+                                .map(|err| (block.id, err));
+                        })
+                        .collect::<Vec<(u64, BlockError)>>();
+                }))
+                .collect();
+            for handle in handles {
+                errors.extend(handle.join().expect("validation thread panicked"));
+            }
+        });
+        let anchor_errors = (0..self.model_anchors.len())
+            .filter_map(|index| self.validate_anchor(index).err().map(|err| (index, err)))
+            .collect();
+        return ValidationReport { blocks_checked: len, errors, anchors_checked: self.model_anchors.len(), anchor_errors };
+    }
+
+    //Hash the anchor at index must link to
+    fn anchor_previous_hash(&self, index: usize) -> String {
+        return match index {
+            0 => self.params.hash(),
+            _ => self.model_anchors[index - 1].hash.clone()
+        };
+    }
+
+    //Checks a model anchor's link to the one before, its mined hash and the block it commits to
+    fn validate_anchor(&self, index: usize) -> Result<(), BlockError> {
+        let anchor = &self.model_anchors[index];
+        if anchor.previous_hash != self.anchor_previous_hash(index) {
+            return Err(BlockError::InvalidPreviousHash);
+        }
+        if generate_anchor_hash(anchor) != anchor.hash {
+            return Err(BlockError::IncorrectHash);
+        }
+        if !anchor.hash.starts_with(&self.params.difficulty_prefix()) {
+            return Err(BlockError::InsufficientDifficulty);
+        }
+        if self.blocks.get(anchor.block_id as usize).is_none_or(|block| block.hash != anchor.block_hash) {
+            return Err(BlockError::AnchorOffChain);
+        }
+        return Ok(());
+    }
+
+    //Iterates over the blocks in chain order without copying them
+    pub fn iter_blocks(&self) -> std::slice::Iter<'_, Block<T>> {
+        return self.blocks.iter();
+    }
+
+    //Iterates over the records in chain order without copying them
+    pub fn records(&self) -> impl ExactSizeIterator<Item = &T> {
+        return self.blocks.iter().map(|block| &block.payload);
+    }
+
+    //Number of blocks mined under each schema version
+    pub fn schema_versions(&self) -> BTreeMap<u32, usize> {
+        let mut counts: BTreeMap<u32, usize> = BTreeMap::new();
+        for block in &self.blocks {
+            *counts.entry(block.schema_version).or_default() += 1;
+        }
+        return counts;
+    }
+
+    //Block at a height
+    pub fn block(&self, id: u64) -> Option<&Block<T>> {
+        return self.blocks.get(id as usize).filter(|block| block.id == id);
+    }
+
+    pub fn block_by_hash(&self, hash: &str) -> Option<&Block<T>> {
+        return self.blocks.iter().find(|block| block.hash == hash);
+    }
+
+    //Every block holding a record with this id, oldest first
+    pub fn blocks_for(&self, id: &str) -> Vec<&Block<T>> {
+        return self.record_index.get(id)
+            .map(|ids| ids.iter().filter_map(|id| self.block(*id)).collect())
+            .unwrap_or_default();
+    }
+
+    //Most recent record with this id, the one in force when amendments are allowed
+    pub fn latest_record(&self, id: &str) -> Option<&T> {
+        return self.record_index.get(id)
+            .and_then(|ids| ids.last())
+            .and_then(|id| self.block(*id))
+            .map(|block| &block.payload);
+    }
+
+    //The latest record of every id in chain order, one per id however many times it was amended
+    pub fn latest_records(&self) -> impl Iterator<Item = &T> {
+        return self.blocks.iter()
+            .filter(|block| self.record_index.get(block.payload.id()).and_then(|ids| ids.last()) == Some(&block.id))
+            .map(|block| &block.payload);
+    }
+
+    //Chain parameters, blocks, model anchors and privacy spending as JSON. Subscriptions are not saved
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        return serde_json::to_string(self);
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let mut blockchain: Self = serde_json::from_str(json)?;
+        blockchain.rebuild_record_index();
+        return Ok(blockchain);
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)?;
+        return Ok(());
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        return Ok(Self::from_json(&fs::read_to_string(path)?)?);
+    }
+
+    //Writes the blocks flattened to rows for other tools, returns the number of rows written
+    pub fn export<W: Write + Send>(&self, options: &ExportOptions, writer: W) -> Result<usize, ExportError> {
+        return super::export::export(self, options, writer);
+    }
+}
+
+#[allow(dead_code)]
+impl Blockchain<Patient> {
+    //Given number of chunks, divides up csv lines accordingly
+    pub fn split_into_chunks(&self, file_path_: &String, num_chunks: usize) 
+            -> Result<Vec<Vec<csv::StringRecord>>, csv::Error> {
+        let mut reader = csv::Reader::from_path(file_path_)?;
+        let mut chunks: Vec<Vec<csv::StringRecord>> = vec![Vec::new(); num_chunks];
+        for (idx, record) in reader.records().enumerate() {
+            chunks[idx % num_chunks].push(record?);
+        }
+        return Ok(chunks);
+    }
+
+    //Returns a vector of join handles with each handle assigned a chunk
+    pub fn multi_threaded_reader(&self, file_path_: &String, num_chunks: usize)
+            -> Result<ChunkReaders, csv::Error> {
+        let (tx,rx) = mpsc::channel();
+        let mut handles = Vec::new();
+        let chunks = self.split_into_chunks(file_path_, num_chunks)?;
+        
+        let m = MultiProgress::new();
+        let sty = ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+        ).unwrap()
+        .progress_chars("##-");
+        let mut pb_vec: Vec<ProgressBar> = Vec::new();
+        for chunk in chunks.iter().take(num_chunks) {
+            let pb = m.add(ProgressBar::new(chunk.len().try_into().unwrap()));
+            pb.set_style(sty.clone());
+            pb_vec.push(pb);
+        }
+
+        println!("\nLOADING IN PATIENTS");
+        for (chunk, pb) in chunks.iter().zip(&pb_vec) {
+            let owned_chunk = chunk.clone();
+            let tx_clone = tx.clone();
+            let pb_clone = pb.clone();
+            let m_clone = m.clone();
+            let h = thread::spawn(move || {
+                let result = string_reader(&owned_chunk, &pb_clone, &m_clone);
+                tx_clone.send(result).unwrap();
+            });
+            handles.push(h);
+        }
+        m.println("").unwrap();
+        return Ok((handles, rx));
+    }
+
+    //Each thread returns a vector of patients after reading the csv in parallel. Adds each of the patients
+    pub fn thread_reducer(&mut self, receivers: ChunkReaders) {
+        let (_, results) = receivers;
+        let pb = ProgressBar::new(NUM_OF_ROWS_COVID);
+        let sty = ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:60.green} {pos:>7}/{len:7} {msg}",
+        ).unwrap();
+        pb.set_style(sty);
+        
+        while let Ok(patients) = results.recv() {
+            for patient in patients {
+                self.add_patient_struct(patient);
+                pb.inc(1);
+            }
+        }
+        pb.finish_with_message("done");
+    }
+
+    //Calls threading process
+    pub fn csv_to_blockchain(&mut self, file_path_: &String) -> Result<(), Box<dyn Error>> {
+        return self.csv_to_blockchain_threaded(file_path_, 8);
+    }
+
+    //Reads the CSV with num_chunks reader threads
+    pub fn csv_to_blockchain_threaded(&mut self, file_path_: &String, num_chunks: usize) -> Result<(), Box<dyn Error>> {
+        if !self.blocks.is_empty() {
+            return Err("cannot read a CSV into a non-empty blockchain".into());
+        }
+        let readers = self.multi_threaded_reader(file_path_, num_chunks.max(1))?;
+        self.thread_reducer(readers);
+        return Ok(());
+    }
+
+    //Single threaded approach to reading only a slice of the CSV. Fails when the slice is empty or runs past the file
+    pub fn csv_to_blockchain_range(&mut self, file_path_: &String, start: usize, length: usize) -> Result<(), Box<dyn Error>> {    
+        if length == 0 {
+            return Err("need to read at least one patient row".into());
+        }
+        let mut reader = csv::Reader::from_path(file_path_)?;
+        let records = reader.records().collect::<Result<Vec<csv::StringRecord>, csv::Error>>()?;
+        if start.checked_add(length).is_none_or(|end| end > records.len()) {
+            return Err(format!("rows {} to {} are out of range, {} has {} patient rows", start, start.saturating_add(length - 1),
+                file_path_, records.len()).into());
+        }
+        let slice = &records[start..start + length];
+        let pb = ProgressBar::new(length.try_into().unwrap());
+        let sty = ProgressStyle::with_template(
+            "[{elapsed_precise}] {bar:60.green} {pos:>7}/{len:7} {msg}",
+        ).unwrap();
+        pb.set_style(sty);
+
+        println!("\nCREATING BLOCKCHAIN FROM PATIENT {} TO {}", start, start + length - 1);
+        for record in slice {
+            match Patient::from_record(record) {
+                Ok(patient) => self.add_patient_struct(patient),
+                Err(err) => error!("skipping patient record {}: {}", record.get(0).unwrap_or(""), err)
+            }
+            pb.inc(1);
+        }
+        pb.finish_with_message("done");
+        return Ok(());
+    }
+
+    //Online learner caught up with the current chain, plus the subscription that keeps it current via sync
+    pub fn online_learner(&mut self, features: &[Feature], outcome: Outcome) -> (OnlineLearner, Receiver<Block<Patient>>) {
+        let mut learner = OnlineLearner::new(features, outcome);
+        for block in &self.blocks {
+            learner.observe(block);
+        }
+        return (learner, self.subscribe());
+    }
+
+    //Given the dataset columns of a patient, parses them into a Patient and adds them to the blockchain
+    pub fn add_patient(&mut self, columns: &[&str]) -> Result<(), PatientError> {
+        self.add_patient_struct(Patient::from_columns(columns)?);
+        return Ok(());
+    }
+
+    //Adds patient to blockchain
+    pub fn add_patient_struct(&mut self, patient: Patient) {
+        self.add_record(patient);
+    }
+
+    //Maps the patients of a FHIR bundle file and adds them to the blockchain, reporting what could not be mapped
+    pub fn import_fhir(&mut self, path: &str) -> Result<MappingReport, Box<dyn Error>> {
+        let (patients, mut report) = read_bundle(&fs::read_to_string(path)?)?;
+        let height = self.blocks.len();
+        for patient in patients {
+            self.add_patient_struct(patient);
+        }
+        report.added = self.blocks.len() - height;
+        return Ok(report);
+    }
+
+    //Iterates over the patient records in chain order without copying them
+    pub fn patients(&self) -> impl ExactSizeIterator<Item = &Patient> {
+        return self.records();
+    }
+
+    //Design matrix of every patient on the chain, filled straight from the borrowed records
+    pub fn feature_matrix(&self, features: &[Feature]) -> Array2<f64> {
+        return design_matrix(self.patients(), features);
+    }
+
+    //Builds the design matrix, death outcomes and coefficient names for the regression
+    pub fn regression_data(&self) -> (Array2<f64>, Array1<f64>, Vec<String>) {
+        return self.regression_data_for(&DEFAULT_FEATURES, Outcome::Died);
+    }
+
+    //Same as regression_data for a chosen set of features and target, patients with an unknown target are skipped
+    pub fn regression_data_for(&self, features: &[Feature], outcome: Outcome) -> (Array2<f64>, Array1<f64>, Vec<String>) {
+        let (x_arr, y_arr) = training_data(self.patients(), features, outcome);
+        return (x_arr, y_arr, feature_names(features));
+    }
+
+    //Fits outcome against patient risk factors and returns the coefficients with their inference statistics
+    pub fn run_regression(&self, outcome: Outcome, options: &SolverOptions) -> Result<RegressionResult, RegressionError> {
+        let (x_arr, y_arr, names) = self.regression_data_for(&DEFAULT_FEATURES, outcome);
+        if y_arr.len() < 6 {
+            return Err(RegressionError::TooFewRows);
+        }
+        return logistic_regression(&x_arr, &y_arr, &names, options);
+    }
+
+    //Fits the penalty in options over a grid of num strengths, from the strongest worth fitting downwards
+    pub fn run_regression_path(&self, outcome: Outcome, options: &SolverOptions, num: usize) -> Result<Vec<(f64, RegressionResult)>, RegressionError> {
+        let (x_arr, y_arr, names) = self.regression_data_for(&DEFAULT_FEATURES, outcome);
+        if y_arr.len() < 6 {
+            return Err(RegressionError::TooFewRows);
+        }
+        let strengths = penalty_grid(&x_arr, &y_arr, &options.penalty, num, 0.001);
+        return regularization_path(&x_arr, &y_arr, &names, options, &strengths);
+    }
+
+    //Fits on a seeded random share of the chain and reports metrics on the held-out test_fraction
+    pub fn evaluate_regression(&self, outcome: Outcome, options: &SolverOptions, test_fraction: f64, seed: u64, thresholds: &[f64]) -> Result<Evaluation, RegressionError> {
+        let (x_arr, y_arr, names) = self.regression_data_for(&DEFAULT_FEATURES, outcome);
+        return holdout(&x_arr, &y_arr, &names, options, test_fraction, seed, thresholds);
+    }
+
+    //Seeded k-fold cross-validation of the regression over the chain
+    pub fn cross_validate_regression(&self, outcome: Outcome, options: &SolverOptions, k: usize, seed: u64, thresholds: &[f64]) -> Result<Vec<Evaluation>, RegressionError> {
+        let (x_arr, y_arr, names) = self.regression_data_for(&DEFAULT_FEATURES, outcome);
+        return cross_validate(&x_arr, &y_arr, &names, options, k, seed, thresholds);
+    }
+
+    //Trains each classifier on the same seeded split of the chain and reports its held-out metrics
+    pub fn compare_classifiers(&self, classifiers: &mut [Box<dyn Classifier>], features: &[Feature], outcome: Outcome, test_fraction: f64, seed: u64, threshold: f64)
+        -> Result<Vec<(String, Metrics)>, RegressionError> {
+        let (x_arr, y_arr, _) = self.regression_data_for(features, outcome);
+        return compare_classifiers(classifiers, &x_arr, &y_arr, test_fraction, seed, threshold);
+    }
+
+    //Deals the chain's patients round-robin to num_hospitals simulated hospitals, like split_into_chunks does with CSV rows
+    pub fn hospital_nodes(&self, num_hospitals: usize, features: &[Feature], outcome: Outcome) -> Vec<HospitalNode> {
+        let mut assigned: Vec<Vec<&Patient>> = vec![Vec::new(); num_hospitals];
+        for (idx, block) in self.blocks.iter().enumerate() {
+            assigned[idx % num_hospitals].push(&block.payload);
+        }
+        return assigned.iter().enumerate()
+            .map(|(i, patients)| HospitalNode::from_patients(format!("hospital-{}", i), patients, features, outcome))
+            .collect();
+    }
+
+    //Fits the same model as run_regression with each simulated hospital only sharing aggregated updates
+    pub fn run_federated_regression(&self, num_hospitals: usize, outcome: Outcome, options: &SolverOptions) -> Result<RegressionResult, RegressionError> {
+        if self.blocks.len() < 6 || num_hospitals == 0 {
+            return Err(RegressionError::TooFewRows);
+        }
+        let nodes = self.hospital_nodes(num_hospitals, &DEFAULT_FEATURES, outcome);
+        return federated_regression(&nodes, &feature_names(&DEFAULT_FEATURES), options);
+    }
+
+    //Sets the total (epsilon, delta) differentially private queries on the chain may spend. It is saved with the chain
+    pub fn set_privacy_budget(&mut self, total_epsilon: f64, total_delta: f64) -> Result<(), PrivacyError> {
+        return self.privacy_budget.set_totals(total_epsilon, total_delta);
+    }
+
+    //Number of deaths on the chain with Laplace noise
+    //Only the latest record of each patient counts, so one patient changes the count by at most one
+    pub fn private_death_count(&mut self, epsilon: f64) -> Result<f64, PrivacyError> {
+        let deaths = self.latest_records().filter(|patient| patient.died()).count();
+        return self.privacy_budget.noisy_count("death count", deaths, epsilon);
+    }
+
+    //Mean patient age with Laplace noise, ages clamped to [0, 120]
+    pub fn private_mean_age(&mut self, epsilon: f64) -> Result<f64, PrivacyError> {
+        let ages: Vec<f64> = self.latest_records().map(|patient| patient.feature(Feature::Age)).collect();
+        return self.privacy_budget.noisy_mean("mean age", &ages, 0.0, 120.0, epsilon);
+    }
+
+    //Epsilon-DP coefficients of run_regression's model by objective perturbation
+    pub fn run_private_regression(&mut self, epsilon: f64, lambda: f64) -> Result<Array1<f64>, PrivacyError> {
+        let (x_arr, y_arr, _) = self.regression_data();
+        // Every default feature is 0 or 1, so no row is longer than the square root of their count
+        let row_norm_bound = (DEFAULT_FEATURES.len() as f64).sqrt();
+        return self.privacy_budget.private_regression("logistic regression", &x_arr, &y_arr, epsilon, lambda, row_norm_bound);
+    }
+
+    //Latest admission or death date on the chain, the default end of follow-up
+    pub fn last_recorded_date(&self) -> Option<NaiveDate> {
+        return self.blocks.iter()
+            .flat_map(|block| [Some(block.payload.entry_date), block.payload.date_died])
+            .flatten()
+            .max();
+    }
+
+    //Survival records of every patient with usable dates, paired with the patient they came from
+    pub fn survival_records(&self, origin: TimeOrigin, censor_date: Option<NaiveDate>) -> Vec<(&Patient, SurvivalRecord)> {
+        let censor_date = match censor_date.or_else(|| self.last_recorded_date()) {
+            Some(date) => date,
+            None => return vec![]
+        };
+        return self.blocks.iter()
+            .filter_map(|block| {
+                let patient = &block.payload;
+                return patient.survival_record(origin, censor_date).map(|record| (patient, record));
+            })
+            .collect();
+    }
+
+    //Kaplan-Meier curves of time to death, one per label returned by group
+    pub fn kaplan_meier_by(&self, origin: TimeOrigin, censor_date: Option<NaiveDate>, group: impl Fn(&Patient) -> String)
+        -> BTreeMap<String, KaplanMeierCurve> {
+        let labelled: Vec<(String, SurvivalRecord)> = self.survival_records(origin, censor_date).into_iter()
+            .map(|(patient, record)| (group(patient), record))
+            .collect();
+        return kaplan_meier_by_group(&labelled);
+    }
+
+    //Cox proportional hazards of time to death on the given features, the intercept is dropped if present
+    pub fn run_cox(&self, origin: TimeOrigin, censor_date: Option<NaiveDate>, features: &[Feature], options: &SolverOptions)
+        -> Result<CoxResult, RegressionError> {
+        let covariates: Vec<Feature> = features.iter().copied().filter(|f| *f != Feature::Intercept).collect();
+        let (patients, records): (Vec<&Patient>, Vec<SurvivalRecord>) = self.survival_records(origin, censor_date).into_iter().unzip();
+        let x_arr = design_matrix(patients.into_iter(), &covariates);
+        return cox_regression(&x_arr, &records, &feature_names(&covariates), options);
+    }
+
+    //Fits a model of outcome on the given features that can be saved, reloaded and used to score new patients
+    pub fn train_model(&self, features: &[Feature], outcome: Outcome, options: &SolverOptions) -> Result<LogisticModel, RegressionError> {
+        //The model records the head it was trained up to, an empty chain has none
+        let head = self.blocks.last().ok_or(RegressionError::TooFewRows)?;
+        let (x_arr, y_arr, names) = self.regression_data_for(features, outcome);
+        if y_arr.len() < features.len() {
+            return Err(RegressionError::TooFewRows);
+        }
+        let result = logistic_regression(&x_arr, &y_arr, &names, options)?;
+        return Ok(LogisticModel::new(outcome, features, &result, head.id, head.hash.clone()));
+    }
+
+    //Records the model hash against the block it was trained up to
+    pub fn anchor_model(&mut self, model: &LogisticModel) -> Option<ModelAnchor> {
+        let block = self.blocks.get(model.chain_height as usize)?;
+        if block.hash != model.chain_head_hash {
+            return None;
+        }
+        let mut anchor = ModelAnchor {
+            model_hash: model.hash(),
+            block_id: block.id,
+            block_hash: block.hash.clone(),
+            timestamp: Utc::now().timestamp(),
+            previous_hash: self.anchor_previous_hash(self.model_anchors.len()),
+            nonce: 0,
+            hash: String::new()
+        };
+        mine_anchor(&mut anchor, &self.params.difficulty_prefix());
+        self.model_anchors.push(anchor.clone());
+        return Some(anchor);
+    }
+
+    //Mines the anchors saved before anchors were hashed, in order, returns how many were sealed. Anchors whose
+    //block is no longer on the chain are left as they are and fail validation
+    pub fn seal_anchors(&mut self) -> usize {
+        let mut sealed = 0;
+        for index in 0..self.model_anchors.len() {
+            let anchor = &self.model_anchors[index];
+            let on_chain = self.blocks.get(anchor.block_id as usize).is_some_and(|block| block.hash == anchor.block_hash);
+            if anchor.hash.is_empty() && on_chain {
+                let previous_hash = self.anchor_previous_hash(index);
+                let prefix = self.params.difficulty_prefix();
+                let anchor = &mut self.model_anchors[index];
+                anchor.previous_hash = previous_hash;
+                mine_anchor(anchor, &prefix);
+                sealed += 1;
+            }
+        }
+        return sealed;
+    }
+
+    //Checks that the model has a valid anchor committing it to the block it claims to be trained on
+    pub fn verify_model(&self, model: &LogisticModel) -> bool {
+        let model_hash = model.hash();
+        return self.model_anchors.iter().enumerate().any(|(index, anchor)| {
+            anchor.model_hash == model_hash && anchor.block_id == model.chain_height && self.validate_anchor(index).is_ok()
+        });
+    }
+
+    // println!("{:?},\nPrevious Hash: {},\nHash: {},\nNonce: {}\n", payload, previous_hash, hash, nonce);
+}
+
+//Uses RNG to generate nonce value
+fn generate_nonce() -> u64 {
+    let mut rng: ThreadRng = rand::thread_rng();
+    
+    let random_number_64: u64 = rng.gen();
+    return random_number_64;
+}
+
+//Generates a hash using SHA256 impl. Blocks without a hospital leave it out, as blocks mined before they named one did
+fn generate_hash(id: u64, previous_hash: String, timestamp: i64, nonce: u64, schema_version: u32, payload_digest: String, hospital: Option<&str>) -> String {
+    let mut data = serde_json::json!({
+        "id": id, 
+        "previous_hash": previous_hash,
+        "nonce": nonce,
+        "timestamp": timestamp,
+        "schema_version": schema_version,
+        "payload_digest": payload_digest
+    });
+    if let Some(hospital) = hospital {
+        data["hospital"] = Value::from(hospital);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(data.to_string().as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//Hash of a model anchor, committing to everything in it but the hash itself
+fn generate_anchor_hash(anchor: &ModelAnchor) -> String {
+    let data = serde_json::json!({
+        "model_hash": anchor.model_hash,
+        "block_id": anchor.block_id,
+        "block_hash": anchor.block_hash,
+        "timestamp": anchor.timestamp,
+        "previous_hash": anchor.previous_hash,
+        "nonce": anchor.nonce
+    });
+    let mut hasher = Sha256::new();
+    hasher.update(data.to_string().as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//Picks nonces until the anchor's hash meets the difficulty, as mine_block does for blocks
+fn mine_anchor(anchor: &mut ModelAnchor, difficulty_prefix: &str) {
+    loop {
+        anchor.nonce = generate_nonce();
+        anchor.hash = generate_anchor_hash(anchor);
+        if anchor.hash.starts_with(difficulty_prefix) {
+            return;
+        }
+    }
+}
+
+//Hash of blocks mined before payloads were hashed, which only committed to the patient id
+fn generate_legacy_hash(id: u64, previous_hash: String, timestamp: i64, nonce: u64, patient_id: String) -> String {
+    let data = serde_json::json!({
+        "id": id, 
+        "previous_hash": previous_hash,
+        "nonce": nonce,
+        "timestamp": timestamp,
+        "patient_id": patient_id
+    });
+    let mut hasher = Sha256::new();
+    hasher.update(data.to_string().as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//Hash of blocks mined after payloads were hashed but before blocks carried a schema version
+fn generate_unversioned_hash(id: u64, previous_hash: String, timestamp: i64, nonce: u64, payload_digest: String) -> String {
+    let data = serde_json::json!({
+        "id": id, 
+        "previous_hash": previous_hash,
+        "nonce": nonce,
+        "timestamp": timestamp,
+        "payload_digest": payload_digest
+    });
+    let mut hasher = Sha256::new();
+    hasher.update(data.to_string().as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//SHA256 of a payload serialized with its fields in declaration order
+fn field_order_digest<T: Serialize>(payload: &T) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_string(payload).expect("payload serializes").as_bytes());
+    return hex::encode(hasher.finalize());
+}
+
+//Mines a block and returns nonce and hash for specified difficulty 
+fn mine_block(id: u64, timestamp: i64, previous_hash: &str, schema_version: u32, payload_digest: String, hospital: Option<&str>,
+    difficulty_prefix: &str) -> (u64, String) {
+    let mut nonce = generate_nonce();
+    loop {
+        let hash = generate_hash(id, previous_hash.to_string(), timestamp, nonce, schema_version, payload_digest.clone(), hospital);
+        if hash.starts_with(difficulty_prefix) {
+            return (nonce, hash);
+        }
+        nonce = generate_nonce();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::patient::{CovidResult, Flag, PatientType, Sex};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_generate_hash() {
+        assert_eq!(generate_hash(1, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string(), 1669749953, 0, 1, "16169f".to_string(), None), 
+            generate_hash(1, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string(), 1669749953, 0, 1, "16169f".to_string(), None));
+        assert_ne!(generate_hash(1, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string(), 1669749953, 0, 1, "16169f".to_string(), None), 
+            generate_hash(2, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9".to_string(), 1669749953, 0, 1, "16169f".to_string(), None));
+        assert_ne!(generate_hash(0, "".to_string(), 0, 0, 1, "16169f".to_string(), None), generate_hash(1, "a".to_string(), 1, 0, 1, "16169f".to_string(), None));
+        assert_ne!(generate_hash(0, "".to_string(), 0, 0, 1, "16169f".to_string(), None), generate_hash(0, "".to_string(), 0, 0, 1, "16169f".to_string(), Some("")));
+    }
+
+    fn test_patient(id: &str, sex: char, pneumonia: u8, diabetes: u8, died: bool) -> Patient {
+        let flag = |code: u8| if code == 1 { Flag::Yes } else { Flag::No };
+        return Patient{id: id.to_string(), sex: if sex == '2' { Sex::Male } else { Sex::Female }, patient_type: PatientType::Inpatient,
+            entry_date: NaiveDate::from_ymd_opt(2020, 5, 10).unwrap(), date_symptoms: NaiveDate::from_ymd_opt(2020, 5, 8).unwrap(),
+            date_died: if died { NaiveDate::from_ymd_opt(2020, 5, 20) } else { None }, intubed: Flag::No, pneumonia: flag(pneumonia), age: 50,
+            pregnancy: Flag::No, diabetes: flag(diabetes), copd: Flag::No, asthma: Flag::No, inmsupr: Flag::No, hypertension: Flag::No,
+            other_disease: Flag::No, cardiovascular: Flag::No, obesity: Flag::No, renal_chronic: Flag::No, tobacco: Flag::No,
+            contact_other_covid: Flag::No, covid_res: CovidResult::Positive, icu: Flag::No};
+    }
+
+    #[test]
+    fn online_learner_test() {
+        let features = [Feature::Intercept, Feature::Pneumonia];
+        let mut blockchain = Blockchain::new();
+        assert!(matches!(blockchain.train_model(&[], Outcome::Died, &SolverOptions::default()), Err(RegressionError::TooFewRows)));
+        blockchain.add_patient_struct(test_patient("a", '1', 1, 2, true));
+        let (mut learner, rx) = blockchain.online_learner(&features, Outcome::Died);
+        let rest = [("b", 1, false), ("c", 2, false), ("d", 2, true), ("e", 1, true), ("f", 2, false), ("g", 1, false)];
+        for (id, pneumonia, died) in rest {
+            blockchain.add_patient_struct(test_patient(id, '2', pneumonia, 2, died));
+        }
+        assert_eq!(learner.sync(&rx), 6);
+
+        let options = SolverOptions::default();
+        let online = learner.snapshot(&options).unwrap();
+        let retrained = blockchain.train_model(&features, Outcome::Died, &options).unwrap();
+        assert_eq!(online.chain_head_hash, retrained.chain_head_hash);
+        for i in 0..2 {
+            assert!((online.coefficients[i] - retrained.coefficients[i]).abs() < 1e-8);
+        }
+        let earlier = learner.snapshot_at(4, &options).unwrap();
+        assert_eq!(earlier.chain_height, 4);
+        assert_eq!(earlier.trained_rows, 5);
+    }
+
+    #[test]
+    fn validate_chain_test() {
+        let mut blockchain = Blockchain::new();
+        for id in ["a", "b", "c", "d"] {
+            blockchain.add_patient_struct(test_patient(id, '1', 2, 2, false));
+        }
+        let shared = &blockchain;
+        assert!(shared.validate_chain());
+        assert_eq!(shared.patients().map(|patient| patient.id.as_str()).collect::<Vec<&str>>(), ["a", "b", "c", "d"]);
+        assert_eq!(shared.feature_matrix(&[Feature::Intercept, Feature::Male]).dim(), (4, 2));
+
+        blockchain.blocks[2].timestamp += 1;
+        assert!(!blockchain.validate_chain());
+        blockchain.blocks[0].previous_hash = "forged".to_string();
+        let report = blockchain.validate_chain_report(3);
+        assert_eq!(report.errors, vec![(0, BlockError::InvalidGenesis), (2, BlockError::IncorrectHash)]);
+    }
+
+    #[test]
+    fn chain_params_test() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_patient_struct(test_patient("a", '1', 2, 2, false));
+        blockchain.add_patient_struct(test_patient("b", '1', 2, 2, false));
+        assert_eq!(blockchain.blocks[0].previous_hash, ChainParams::default().hash());
+        assert!(blockchain.validate_chain());
+
+        let other_network = ChainParams { chain_id: "other".to_string(), ..ChainParams::default() };
+        let mut imported = Blockchain::with_params(other_network);
+        imported.blocks = blockchain.blocks.clone();
+        assert_eq!(imported.validate_chain_report(1).errors, vec![(0, BlockError::InvalidGenesis)]);
+
+        let harder = ChainParams { difficulty: 8, ..ChainParams::default() };
+        let mut imported = Blockchain::with_params(harder.clone());
+        imported.blocks = blockchain.blocks.clone();
+        imported.blocks[0].previous_hash = harder.hash();
+        assert_eq!(imported.validate_chain_report(1).errors[0], (0, BlockError::IncorrectHash));
+    }
+
+    #[test]
+    fn model_anchor_test() {
+        let mut blockchain = Blockchain::new();
+        for id in ["a", "b", "c"] {
+            blockchain.add_patient_struct(test_patient(id, '1', 2, 2, false));
+        }
+        let hashes: Vec<String> = blockchain.iter_blocks().map(|block| block.hash.clone()).collect();
+        let model = |height: usize, coefficient: f64| LogisticModel { outcome: Outcome::Died, features: vec![Feature::Intercept],
+            coefficients: vec![coefficient], trained_rows: height + 1, chain_height: height as u64, chain_head_hash: hashes[height].clone() };
+        let (first, second) = (model(1, -1.0), model(2, -2.0));
+        blockchain.anchor_model(&first).unwrap();
+        blockchain.anchor_model(&second).unwrap();
+        assert_eq!(blockchain.model_anchors[0].previous_hash, blockchain.params.hash());
+        assert!(blockchain.validate_chain() && blockchain.verify_model(&first) && blockchain.verify_model(&second));
+
+        //An anchor edited to commit to another model no longer matches its hash
+        let mut forged = blockchain.model_anchors.clone();
+        forged[0].model_hash = model(1, -3.0).hash();
+        let mut tampered = Blockchain::<Patient>::from_json(&blockchain.to_json().unwrap()).unwrap();
+        tampered.model_anchors = forged;
+        assert_eq!(tampered.validate_chain_report(1).anchor_errors, vec![(0, BlockError::IncorrectHash)]);
+        assert!(!tampered.verify_model(&model(1, -3.0)));
+        //Dropping an anchor breaks the link of the next
+        tampered.model_anchors = blockchain.model_anchors[1..].to_vec();
+        assert_eq!(tampered.validate_chain_report(1).anchor_errors, vec![(0, BlockError::InvalidPreviousHash)]);
+
+        //Anchors saved before anchors were hashed fail validation until they are sealed
+        let mut saved: Value = serde_json::from_str(&blockchain.to_json().unwrap()).unwrap();
+        for anchor in saved["model_anchors"].as_array_mut().unwrap() {
+            for field in ["previous_hash", "nonce", "hash"] {
+                anchor.as_object_mut().unwrap().remove(field);
+            }
+        }
+        let mut old = Blockchain::<Patient>::from_json(&saved.to_string()).unwrap();
+        assert!(!old.validate_chain());
+        assert_eq!(old.seal_anchors(), 2);
+        assert!(old.validate_chain() && old.verify_model(&second));
+    }
+
+    #[test]
+    fn hospital_test() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_patient_struct(test_patient("a", '1', 2, 2, false));
+        blockchain.hospital = Some("Hospital General".to_string());
+        blockchain.add_patient_struct(test_patient("b", '1', 2, 2, false));
+        let saved: Blockchain = serde_json::from_str(&serde_json::to_string(&blockchain).unwrap()).unwrap();
+        assert_eq!(saved.blocks.iter().map(|block| block.hospital.as_deref()).collect::<Vec<_>>(), [None, Some("Hospital General")]);
+        assert!(saved.validate_chain_report(1).is_valid());
+
+        //The hash commits to the hospital, so a block cannot be credited to another one
+        let mut tampered = saved;
+        tampered.blocks[1].hospital = Some("Other".to_string());
+        assert!(!tampered.validate_chain_report(1).is_valid());
+    }
+
+    #[test]
+    fn duplicate_policy_test() {
+        let mut blockchain = Blockchain::new();
+        for (id, died) in [("a", false), ("b", false), ("a", true)] {
+            blockchain.add_patient_struct(test_patient(id, '1', 2, 2, died));
+        }
+        assert_eq!(blockchain.blocks.len(), 2);
+
+        let amend = ChainParams { duplicate_policy: DuplicatePolicy::Amend, ..ChainParams::default() };
+        let mut blockchain = Blockchain::with_params(amend);
+        for (id, died) in [("a", false), ("b", false), ("a", true)] {
+            blockchain.add_patient_struct(test_patient(id, '1', 2, 2, died));
+        }
+        assert_eq!(blockchain.blocks.len(), 3);
+        assert!(blockchain.validate_chain());
+        assert!(blockchain.latest_record("a").unwrap().died());
+        assert!(Blockchain::<Patient>::from_json(&blockchain.to_json().unwrap()).unwrap().latest_record("a").unwrap().died());
+
+        assert_eq!(blockchain.latest_records().map(|patient| (patient.id.as_str(), patient.died())).collect::<Vec<_>>(), [("b", false), ("a", true)]);
+        blockchain.set_privacy_budget(1e9, 0.0).unwrap();
+        assert_eq!(blockchain.private_death_count(1e9).unwrap().round(), 1.0);
+
+        //The policy is committed in the genesis block, a node switching to Reject no longer agrees on the chain
+        blockchain.params.duplicate_policy = DuplicatePolicy::Reject;
+        assert_ne!(blockchain.params.hash(), ChainParams { duplicate_policy: DuplicatePolicy::Amend, ..ChainParams::default() }.hash());
+        assert_eq!(blockchain.validate_chain_report(2).errors, vec![(0, BlockError::InvalidGenesis), (2, BlockError::DuplicateRecord)]);
+
+        //A chain from before the policy was hashed keeps allowing repeats, whatever its file says
+        let mut old = Blockchain::<Patient>::from_json(SAVED_CHAINS[0].0).unwrap();
+        assert_eq!(old.params.committed_duplicate_policy(), DuplicatePolicy::AllowWithWarning);
+        old.params.duplicate_policy = DuplicatePolicy::Reject;
+        let repeat = old.blocks[0].payload.clone();
+        old.add_patient_struct(repeat);
+        assert_eq!(old.blocks.len(), 4);
+        assert!(old.validate_chain());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct LabResult {
+        sample_id: String,
+        test: String,
+        value: f64
+    }
+
+    impl Payload for LabResult {
+        type Error = String;
+
+        fn id(&self) -> &str {
+            return &self.sample_id;
+        }
+
+        fn check(&self) -> Result<(), String> {
+            if self.value < 0.0 {
+                return Err(format!("negative {} result", self.test));
+            }
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn generic_payload_test() {
+        let lab = |sample_id: &str, value: f64| LabResult { sample_id: sample_id.to_string(), test: "crp".to_string(), value };
+        let mut labs: Blockchain<LabResult> = Blockchain::new();
+        for result in [lab("s1", 12.0), lab("s2", -1.0), lab("s3", 4.5), lab("s1", 3.0)] {
+            labs.add_record(result);
+        }
+        assert_eq!(labs.records().map(|result| result.id()).collect::<Vec<&str>>(), ["s1", "s3"]);
+        assert!(labs.validate_chain());
+        let loaded: Blockchain<LabResult> = Blockchain::from_json(&labs.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.latest_record("s3").unwrap().value, 4.5);
+
+        labs.blocks[1].payload.value = 45.0;
+        assert_eq!(labs.validate_chain_report(1).errors, vec![(1, BlockError::IncorrectHash)]);
+    }
+
+    #[test]
+    fn csv_ingest_test() {
+        let path = std::env::temp_dir().join(format!("carle_chain_ingest_{}.csv", std::process::id()));
+        let row = |id: &str| format!("{},2,1,10-05-2020,08-05-2020,9999-99-99,97,1,35,2,2,2,2,2,2,2,2,2,2,2,99,1,97", id);
+        fs::write(&path, [crate::patient::COLUMN_NAMES.join(","), row("a"), row("b"), row("c")].join("\n")).unwrap();
+        let csv = path.to_string_lossy().to_string();
+
+        let mut blockchain: Blockchain = Blockchain::new();
+        blockchain.csv_to_blockchain_range(&csv, 1, 2).unwrap();
+        assert_eq!(blockchain.patients().map(|patient| patient.id.as_str()).collect::<Vec<&str>>(), ["b", "c"]);
+        assert!(blockchain.csv_to_blockchain_threaded(&csv, 2).is_err());
+        let mut threaded: Blockchain = Blockchain::new();
+        threaded.csv_to_blockchain_threaded(&csv, 2).unwrap();
+        assert_eq!(threaded.blocks.len(), 3);
+
+        for (start, length) in [(2, 2), (3, 1), (0, 0), (1, usize::MAX)] {
+            assert!(Blockchain::<Patient>::new().csv_to_blockchain_range(&csv, start, length).is_err());
+        }
+        fs::write(&path, [crate::patient::COLUMN_NAMES.join(","), row("a"), "b,2".to_string()].join("\n")).unwrap();
+        assert!(Blockchain::<Patient>::new().csv_to_blockchain_threaded(&csv, 2).is_err());
+        fs::remove_file(&path).unwrap();
+        assert!(Blockchain::<Patient>::new().csv_to_blockchain_range(&csv, 0, 1).is_err());
+        assert!(Blockchain::<Patient>::new().csv_to_blockchain_threaded(&csv, 2).is_err());
+    }
+
+    //Chain files written by earlier versions of the ingest command: dataset codes hashed by patient id on a chain whose
+    //parameters predate the duplicate policy, then with it, typed patients hashed by patient id, then typed patients
+    //hashed by their field order digest, none with schema versions
+    const SAVED_CHAINS: [(&str, u32, HashRule); 4] = [
+        (include_str!("../tests/fixtures/chain_without_duplicate_policy.json"), LEGACY_SCHEMA_VERSION, HashRule::PayloadId),
+        (include_str!("../tests/fixtures/chain_dataset_codes.json"), LEGACY_SCHEMA_VERSION, HashRule::PayloadId),
+        (include_str!("../tests/fixtures/chain_typed_id_hash.json"), FIRST_SCHEMA_VERSION, HashRule::PayloadId),
+        (include_str!("../tests/fixtures/chain_field_order_digest.json"), FIRST_SCHEMA_VERSION, HashRule::FieldOrderDigest)
+    ];
+
+    #[test]
+    fn schema_migration_test() {
+        for (saved, schema_version, hash_rule) in SAVED_CHAINS {
+            let mut migrated = Blockchain::<Patient>::from_json(saved).unwrap();
+            assert_eq!((migrated.blocks[0].schema_version, migrated.blocks[0].hash_rule), (schema_version, hash_rule));
+            assert_eq!((migrated.blocks[0].payload.id.as_str(), migrated.blocks[0].payload.sex), ("p0000", Sex::Male));
+            assert!(migrated.validate_chain());
+            let hashes: Vec<String> = migrated.blocks.iter().map(|block| block.hash.clone()).collect();
+            migrated.add_patient_struct(test_patient("b", '1', 2, 2, false));
+            assert_eq!(migrated.blocks.len(), hashes.len() + 1);
+
+            let reloaded = Blockchain::<Patient>::from_json(&migrated.to_json().unwrap()).unwrap();
+            assert_eq!(reloaded.blocks[..hashes.len()].iter().map(|block| block.hash.clone()).collect::<Vec<String>>(), hashes);
+            assert_eq!(reloaded.blocks[0].original.is_some(), schema_version == LEGACY_SCHEMA_VERSION);
+            assert_eq!(reloaded.blocks.last().unwrap().hash_rule, HashRule::VersionedDigest);
+            assert!(reloaded.validate_chain());
+        }
+
+        let mut tampered: Value = serde_json::from_str(SAVED_CHAINS[0].0).unwrap();
+        tampered["blocks"][1]["patient_info"]["id"] = serde_json::json!("other");
+        let tampered = Blockchain::<Patient>::from_json(&tampered.to_string()).unwrap();
+        assert_eq!(tampered.validate_chain_report(1).errors, vec![(1, BlockError::IncorrectHash)]);
+        let mut tampered: Value = serde_json::from_str(SAVED_CHAINS[3].0).unwrap();
+        tampered["blocks"][1]["payload"]["age"] = serde_json::json!(99);
+        let tampered = Blockchain::<Patient>::from_json(&tampered.to_string()).unwrap();
+        assert_eq!(tampered.validate_chain_report(1).errors, vec![(1, BlockError::IncorrectHash)]);
+
+        let params: Vec<u32> = SAVED_CHAINS.iter().map(|(saved, _, _)| Blockchain::<Patient>::from_json(saved).unwrap().params.params_version).collect();
+        assert_eq!(params, [2, 3, 3, 3]);
+
+        let mut unknown: Value = serde_json::from_str(SAVED_CHAINS[0].0).unwrap();
+        unknown["blocks"][0]["schema_version"] = serde_json::json!(7);
+        assert!(Blockchain::<Patient>::from_json(&unknown.to_string()).is_err());
+    }
+
+    //Chain of mined blocks with the given timestamps, bypassing the checks done when adding blocks
+    fn chain_with_timestamps(params: ChainParams, timestamps: &[i64]) -> Blockchain {
+        let mut blockchain = Blockchain::with_params(params);
+        let mut previous_hash = blockchain.params.hash();
+        for (id, timestamp) in timestamps.iter().enumerate() {
+            let payload = test_patient(&id.to_string(), '1', 2, 2, false);
+            let digest = payload_digest(&serde_json::to_value(&payload).unwrap());
+            let (nonce, hash) = mine_block(id as u64, *timestamp, &previous_hash, Patient::SCHEMA_VERSION, digest, None, &blockchain.params.difficulty_prefix());
+            blockchain.blocks.push(Block { id: id as u64, timestamp: *timestamp, previous_hash, schema_version: Patient::SCHEMA_VERSION,
+                hash_rule: HashRule::VersionedDigest, payload, hospital: None, original: None, nonce, hash: hash.clone() });
+            previous_hash = hash;
+        }
+        return blockchain;
+    }
+
+    #[test]
+    fn timestamp_rules_test() {
+        let timestamps = [100, 200, 300, 250, 150, 400];
+        let monotonic = ChainParams { timestamp_rule: TimestampRule::Monotonic, ..ChainParams::default() };
+        assert_eq!(chain_with_timestamps(monotonic, &timestamps).validate_chain_report(2).errors,
+            vec![(3, BlockError::TimestampTooEarly), (4, BlockError::TimestampTooEarly)]);
+        let median = ChainParams { timestamp_rule: TimestampRule::MedianPast(3), ..ChainParams::default() };
+        assert_eq!(chain_with_timestamps(median, &timestamps).validate_chain_report(2).errors,
+            vec![(4, BlockError::TimestampTooEarly)]);
+
+        let future = Utc::now().timestamp() + 3 * ChainParams::default().max_future_drift;
+        assert_eq!(chain_with_timestamps(ChainParams::default(), &[100, future]).validate_chain_report(1).errors,
+            vec![(1, BlockError::TimestampInFuture)]);
+    }
+
+    #[test]
+    fn generate_nonce_test() {
+        let mut nonces: HashSet<u64> = HashSet::new();
+        for _i in 0..1000 {
+            nonces.insert(generate_nonce());
+        }
+        assert_eq!(nonces.len(), 1000);
+    }
+}
\ No newline at end of file
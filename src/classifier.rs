@@ -0,0 +1,320 @@
+// Classifiers that can be trained on the chain's feature matrix and compared on held-out patients
+
+use ndarray::{Array1, Array2, Axis};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use super::analytics::{evaluate, train_test_split, Metrics};
+use super::logreg::{logistic_regression, predict, RegressionError, SolverOptions};
+
+//A binary classifier predicting the probability of the positive outcome for each row
+pub trait Classifier {
+    fn name(&self) -> String;
+    fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), RegressionError>;
+    fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64>;
+}
+
+//The existing logistic regression behind the Classifier interface
+#[derive(Debug, Clone)]
+pub struct LogisticClassifier {
+    pub options: SolverOptions,
+    pub coefficients: Option<Array1<f64>>
+}
+
+impl LogisticClassifier {
+    pub fn new(options: SolverOptions) -> Self {
+        return Self { options, coefficients: None };
+    }
+}
+
+impl Classifier for LogisticClassifier {
+    fn name(&self) -> String {
+        return "logistic regression".to_string();
+    }
+
+    fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), RegressionError> {
+        let result = logistic_regression(x, y, &[], &self.options)?;
+        self.coefficients = Some(result.coefficients);
+        return Ok(());
+    }
+
+    fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        let beta = self.coefficients.as_ref().expect("classifier is fitted");
+        return predict(beta, x);
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(f64),
+    Split { feature: usize, threshold: f64, left: Box<Node>, right: Box<Node> }
+}
+
+//CART classification tree grown on Gini impurity, leaves hold the share of positive outcomes
+#[derive(Debug, Clone)]
+pub struct DecisionTree {
+    pub max_depth: usize,
+    pub min_samples_split: usize,
+    //Number of features sampled at each split, None considers all of them
+    pub max_features: Option<usize>,
+    pub seed: u64,
+    root: Option<Node>
+}
+
+impl DecisionTree {
+    pub fn new(max_depth: usize, min_samples_split: usize) -> Self {
+        return Self { max_depth, min_samples_split, max_features: None, seed: 0, root: None };
+    }
+
+    fn build(&self, x: &Array2<f64>, y: &Array1<f64>, rows: &mut [usize], depth: usize, rng: &mut StdRng) -> Node {
+        let positives = rows.iter().map(|r| y[*r]).sum::<f64>();
+        let n = rows.len() as f64;
+        let share = positives / n;
+        if depth >= self.max_depth || rows.len() < self.min_samples_split || positives == 0.0 || positives == n {
+            return Node::Leaf(share);
+        }
+
+        let mut features: Vec<usize> = (0..x.ncols()).collect();
+        if let Some(k) = self.max_features {
+            features.shuffle(rng);
+            features.truncate(k.max(1));
+        }
+
+        // Best split minimizes the weighted Gini impurity n_l * g_l + n_r * g_r
+        let mut best: Option<(f64, usize, f64)> = None;
+        for feature in features {
+            rows.sort_by(|a, b| x[[*a, feature]].total_cmp(&x[[*b, feature]]));
+            let mut left_pos = 0.0;
+            for i in 0..rows.len() - 1 {
+                left_pos += y[rows[i]];
+                let (value, next_value) = (x[[rows[i], feature]], x[[rows[i + 1], feature]]);
+                if value == next_value {
+                    continue;
+                }
+                let left_n = (i + 1) as f64;
+                let right_n = n - left_n;
+                let right_pos = positives - left_pos;
+                let impurity = left_n * gini(left_pos / left_n) + right_n * gini(right_pos / right_n);
+                if best.is_none_or(|(b, _, _)| impurity < b) {
+                    best = Some((impurity, feature, (value + next_value) / 2.0));
+                }
+            }
+        }
+
+        // Zero-gain splits are kept so interactions such as XOR can still be found a level further down
+        let (_, feature, threshold) = match best {
+            Some(split) => split,
+            None => return Node::Leaf(share)
+        };
+        let (mut left, mut right): (Vec<usize>, Vec<usize>) = rows.iter().partition(|r| x[[**r, feature]] <= threshold);
+        return Node::Split {
+            feature,
+            threshold,
+            left: Box::new(self.build(x, y, &mut left, depth + 1, rng)),
+            right: Box::new(self.build(x, y, &mut right, depth + 1, rng))
+        };
+    }
+
+    fn fit_rows(&mut self, x: &Array2<f64>, y: &Array1<f64>, rows: &mut [usize], rng: &mut StdRng) -> Result<(), RegressionError> {
+        if rows.is_empty() {
+            return Err(RegressionError::TooFewRows);
+        }
+        self.root = Some(self.build(x, y, rows, 0, rng));
+        return Ok(());
+    }
+
+    fn predict_row(&self, x: &Array2<f64>, row: usize) -> f64 {
+        let mut node = self.root.as_ref().expect("classifier is fitted");
+        loop {
+            match node {
+                Node::Leaf(share) => return *share,
+                Node::Split { feature, threshold, left, right } => {
+                    node = if x[[row, *feature]] <= *threshold { left } else { right };
+                }
+            }
+        }
+    }
+}
+
+fn gini(p: f64) -> f64 {
+    return 2.0 * p * (1.0 - p);
+}
+
+impl Classifier for DecisionTree {
+    fn name(&self) -> String {
+        return format!("decision tree (depth {})", self.max_depth);
+    }
+
+    fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), RegressionError> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut rows: Vec<usize> = (0..x.nrows()).collect();
+        return self.fit_rows(x, y, &mut rows, &mut rng);
+    }
+
+    fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        return (0..x.nrows()).map(|row| self.predict_row(x, row)).collect();
+    }
+}
+
+//Bagged decision trees, each grown on a bootstrap sample with features sampled at every split
+#[derive(Debug, Clone)]
+pub struct RandomForest {
+    pub n_trees: usize,
+    pub max_depth: usize,
+    pub min_samples_split: usize,
+    pub seed: u64,
+    trees: Vec<DecisionTree>
+}
+
+impl RandomForest {
+    pub fn new(n_trees: usize, max_depth: usize, seed: u64) -> Self {
+        return Self { n_trees, max_depth, min_samples_split: 2, seed, trees: vec![] };
+    }
+}
+
+impl Classifier for RandomForest {
+    fn name(&self) -> String {
+        return format!("random forest ({} trees, depth {})", self.n_trees, self.max_depth);
+    }
+
+    fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), RegressionError> {
+        let n = x.nrows();
+        if n == 0 || self.n_trees == 0 {
+            return Err(RegressionError::TooFewRows);
+        }
+        let max_features = ((x.ncols() as f64).sqrt().round() as usize).max(1);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        self.trees.clear();
+        for _ in 0..self.n_trees {
+            let mut tree = DecisionTree { max_features: Some(max_features), ..DecisionTree::new(self.max_depth, self.min_samples_split) };
+            let mut rows: Vec<usize> = (0..n).map(|_| rng.gen_range(0..n)).collect();
+            tree.fit_rows(x, y, &mut rows, &mut rng)?;
+            self.trees.push(tree);
+        }
+        return Ok(());
+    }
+
+    fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        let mut total = Array1::<f64>::zeros(x.nrows());
+        for tree in &self.trees {
+            total += &tree.predict_proba(x);
+        }
+        return total / self.trees.len() as f64;
+    }
+}
+
+//Naive Bayes with a Bernoulli likelihood for 0/1 columns and a Gaussian one for the rest
+#[derive(Debug, Clone, Default)]
+pub struct NaiveBayes {
+    log_prior: [f64; 2],
+    binary: Vec<bool>,
+    //Per class and feature: P(x = 1) for binary columns, (mean, variance) otherwise
+    params: [Vec<(f64, f64)>; 2]
+}
+
+impl NaiveBayes {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    fn log_likelihood(&self, class: usize, row: ndarray::ArrayView1<f64>) -> f64 {
+        let mut total = self.log_prior[class];
+        for (j, value) in row.iter().enumerate() {
+            let (a, b) = self.params[class][j];
+            total += if self.binary[j] {
+                if *value == 1.0 { a.ln() } else { (1.0 - a).ln() }
+            } else {
+                -0.5 * ((2.0 * std::f64::consts::PI * b).ln() + (value - a).powi(2) / b)
+            };
+        }
+        return total;
+    }
+}
+
+impl Classifier for NaiveBayes {
+    fn name(&self) -> String {
+        return "naive bayes".to_string();
+    }
+
+    fn fit(&mut self, x: &Array2<f64>, y: &Array1<f64>) -> Result<(), RegressionError> {
+        let n = x.nrows();
+        if n == 0 {
+            return Err(RegressionError::TooFewRows);
+        }
+        self.binary = x.axis_iter(Axis(1)).map(|col| col.iter().all(|v| *v == 0.0 || *v == 1.0)).collect();
+        for class in 0..2 {
+            let rows: Vec<usize> = (0..n).filter(|r| y[*r] == class as f64).collect();
+            let count = rows.len() as f64;
+            // Laplace smoothing keeps empty classes and unseen values finite
+            self.log_prior[class] = ((count + 1.0) / (n as f64 + 2.0)).ln();
+            self.params[class] = (0..x.ncols()).map(|j| {
+                let values: Vec<f64> = rows.iter().map(|r| x[[*r, j]]).collect();
+                let sum: f64 = values.iter().sum();
+                if self.binary[j] {
+                    return ((sum + 1.0) / (count + 2.0), 0.0);
+                }
+                let mean = if count > 0.0 { sum / count } else { 0.0 };
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count.max(1.0);
+                return (mean, variance.max(1e-9));
+            }).collect();
+        }
+        return Ok(());
+    }
+
+    fn predict_proba(&self, x: &Array2<f64>) -> Array1<f64> {
+        return x.axis_iter(Axis(0)).map(|row| {
+            let diff = self.log_likelihood(0, row) - self.log_likelihood(1, row);
+            return 1.0 / (1.0 + diff.exp());
+        }).collect();
+    }
+}
+
+//Trains every classifier on the same seeded split and scores the held-out rows at threshold
+pub fn compare_classifiers(classifiers: &mut [Box<dyn Classifier>], x: &Array2<f64>, y: &Array1<f64>,
+        test_fraction: f64, seed: u64, threshold: f64) -> Result<Vec<(String, Metrics)>, RegressionError> {
+    let (train, test) = train_test_split(y.len(), test_fraction, seed);
+    let x_train = x.select(Axis(0), &train);
+    let y_train = y.select(Axis(0), &train);
+    let x_test = x.select(Axis(0), &test);
+    let y_test = y.select(Axis(0), &test);
+
+    let mut results = Vec::new();
+    for classifier in classifiers.iter_mut() {
+        classifier.fit(&x_train, &y_train)?;
+        let probs = classifier.predict_proba(&x_test);
+        results.push((classifier.name(), evaluate(&y_test, &probs, threshold)));
+    }
+    return Ok(results);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn tree_learns_interaction_test() {
+        // Outcome is a XOR b, which no single split and no linear model separates
+        let x = array![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0], [0.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+        let y = array![0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0];
+        let mut tree = DecisionTree::new(3, 2);
+        tree.fit(&x, &y).unwrap();
+        assert_eq!(tree.predict_proba(&x), y);
+    }
+
+    #[test]
+    fn classifiers_agree_on_easy_data_test() {
+        let x = array![[1.0, 0.0], [1.0, 0.0], [1.0, 0.0], [1.0, 1.0], [1.0, 1.0], [1.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+        let y = array![0.0, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0];
+        let mut classifiers: Vec<Box<dyn Classifier>> = vec![
+            Box::new(LogisticClassifier::new(SolverOptions::default())),
+            Box::new(RandomForest::new(10, 3, 1)),
+            Box::new(NaiveBayes::new())
+        ];
+        for classifier in classifiers.iter_mut() {
+            classifier.fit(&x, &y).unwrap();
+            let probs = classifier.predict_proba(&x);
+            assert!(probs[3] > probs[0], "{}", classifier.name());
+        }
+    }
+}
@@ -0,0 +1,482 @@
+// Flat exports of a chain for analysis in other tools. Every block becomes one row holding its metadata
+// followed by its payload's fields, with nested fields named by joining their keys with '.'.
+//
+//   block_id, hash, previous_hash, timestamp, schema_version, hospital, <payload fields>
+//
+// hospital is empty for blocks whose node did not name one. A payload field named like a metadata column fails
+// the export instead of replacing the column.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use serde_json::{Map, Value};
+
+use super::blockchain::{Block, Blockchain, Payload};
+
+//Columns every row starts with, before the payload's fields
+pub const METADATA_COLUMNS: [&str; 6] = ["block_id", "hash", "previous_hash", "timestamp", "schema_version", "hospital"];
+
+//Rows per Parquet record batch
+pub const BATCH_ROWS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    JsonLines,
+    Parquet
+}
+
+#[derive(Debug)]
+//Enum describing why an export failed
+pub enum ExportError {
+    UnknownField(String),
+    ColumnCollision(String),
+    InvalidFilter(String),
+    Io(io::Error),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    Arrow(ArrowError),
+    Parquet(ParquetError)
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            ExportError::UnknownField(field) => write!(f, "no field {} on the chain", field),
+            ExportError::ColumnCollision(field) => write!(f, "payload field {} has the name of another column", field),
+            ExportError::InvalidFilter(filter) => write!(f, "invalid filter {:?}, expected e.g. age>=60 or icu=Yes", filter),
+            ExportError::Io(err) => write!(f, "{}", err),
+            ExportError::Csv(err) => write!(f, "{}", err),
+            ExportError::Json(err) => write!(f, "{}", err),
+            ExportError::Arrow(err) => write!(f, "{}", err),
+            ExportError::Parquet(err) => write!(f, "{}", err)
+        };
+    }
+}
+
+impl Error for ExportError {}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> Self {
+        return ExportError::Io(err);
+    }
+}
+
+impl From<csv::Error> for ExportError {
+    fn from(err: csv::Error) -> Self {
+        return ExportError::Csv(err);
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(err: serde_json::Error) -> Self {
+        return ExportError::Json(err);
+    }
+}
+
+impl From<ArrowError> for ExportError {
+    fn from(err: ArrowError) -> Self {
+        return ExportError::Arrow(err);
+    }
+}
+
+impl From<ParquetError> for ExportError {
+    fn from(err: ParquetError) -> Self {
+        return ExportError::Parquet(err);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge
+}
+
+#[derive(Debug, Clone, PartialEq)]
+//Condition a row must meet to be exported. Numbers compare numerically, anything else by its text,
+//which orders ISO dates correctly. A missing value reads as empty text
+pub struct Filter {
+    pub field: String,
+    pub comparison: Comparison,
+    pub value: String
+}
+
+impl Filter {
+    //Parses conditions such as age>=60, icu=Yes or date_died!=
+    pub fn parse(text: &str) -> Result<Filter, ExportError> {
+        //Two character operators first so >= is not read as >
+        let operators = [(">=", Comparison::Ge), ("<=", Comparison::Le), ("!=", Comparison::Ne), ("=", Comparison::Eq), (">", Comparison::Gt), ("<", Comparison::Lt)];
+        for (operator, comparison) in operators {
+            if let Some((field, value)) = text.split_once(operator) {
+                if field.trim().is_empty() {
+                    break;
+                }
+                return Ok(Filter { field: field.trim().to_string(), comparison, value: value.trim().to_string() });
+            }
+        }
+        return Err(ExportError::InvalidFilter(text.to_string()));
+    }
+
+    pub fn matches(&self, cell: &Value) -> bool {
+        let ordering = match (cell.as_f64(), self.value.parse::<f64>()) {
+            (Some(number), Ok(value)) => number.partial_cmp(&value),
+            _ => Some(cell_text(cell).as_str().cmp(self.value.as_str()))
+        };
+        let ordering = match ordering {
+            Some(ordering) => ordering,
+            None => return self.comparison == Comparison::Ne
+        };
+        return match self.comparison {
+            Comparison::Eq => ordering.is_eq(),
+            Comparison::Ne => ordering.is_ne(),
+            Comparison::Lt => ordering.is_lt(),
+            Comparison::Le => ordering.is_le(),
+            Comparison::Gt => ordering.is_gt(),
+            Comparison::Ge => ordering.is_ge()
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+//Which rows and columns to write. Every column is written when fields is None
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub fields: Option<Vec<String>>,
+    pub filters: Vec<Filter>
+}
+
+impl ExportOptions {
+    pub fn new(format: ExportFormat) -> Self {
+        return Self { format, fields: None, filters: vec![] };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+//Narrowest Parquet type holding every value of a column met so far
+enum Kind {
+    Empty,
+    Boolean,
+    Integer,
+    Float,
+    Text
+}
+
+impl Kind {
+    fn with(self, cell: &Value) -> Kind {
+        let kind = match cell {
+            Value::Null => return self,
+            Value::Bool(_) => Kind::Boolean,
+            Value::Number(number) if number.is_i64() => Kind::Integer,
+            Value::Number(_) => Kind::Float,
+            _ => Kind::Text
+        };
+        return match (self, kind) {
+            (Kind::Empty, kind) => kind,
+            (current, kind) if current == kind => kind,
+            (Kind::Integer, Kind::Float) | (Kind::Float, Kind::Integer) => Kind::Float,
+            _ => Kind::Text
+        };
+    }
+
+    //Columns of only booleans become booleans, of only integers 64 bit integers, of any numbers doubles
+    //and anything else text
+    fn data_type(self) -> DataType {
+        return match self {
+            Kind::Boolean => DataType::Boolean,
+            Kind::Integer => DataType::Int64,
+            Kind::Float => DataType::Float64,
+            Kind::Empty | Kind::Text => DataType::Utf8
+        };
+    }
+
+    fn array(self, cells: &[Value]) -> ArrayRef {
+        return match self {
+            Kind::Boolean => Arc::new(cells.iter().map(|cell| cell.as_bool()).collect::<BooleanArray>()),
+            Kind::Integer => Arc::new(cells.iter().map(|cell| cell.as_i64()).collect::<Int64Array>()),
+            Kind::Float => Arc::new(cells.iter().map(|cell| cell.as_f64()).collect::<Float64Array>()),
+            Kind::Empty | Kind::Text => Arc::new(cells.iter().map(|cell| if cell.is_null() { None } else { Some(cell_text(cell)) }).collect::<StringArray>())
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+//Columns of a chain's rows and the types of the values the filters keep, found by a first pass over the
+//blocks so the rows themselves can be written one at a time
+pub struct Layout {
+    pub columns: Vec<String>,
+    kinds: HashMap<String, Kind>
+}
+
+impl Layout {
+    //Columns in the order they are first met
+    pub fn scan<T: Payload>(blockchain: &Blockchain<T>, filters: &[Filter]) -> Result<Layout, ExportError> {
+        let mut columns: Vec<String> = METADATA_COLUMNS.iter().map(|column| column.to_string()).collect();
+        let mut kinds: HashMap<String, Kind> = columns.iter().map(|column| (column.clone(), Kind::Empty)).collect();
+        for block in blockchain.iter_blocks() {
+            let record = block_record(block)?;
+            let kept = filters.iter().all(|filter| filter.matches(record.get(&filter.field).unwrap_or(&Value::Null)));
+            for (key, cell) in &record {
+                let kind = kinds.entry(key.clone()).or_insert_with(|| {
+                    columns.push(key.clone());
+                    return Kind::Empty;
+                });
+                if kept {
+                    *kind = kind.with(cell);
+                }
+            }
+        }
+        let layout = Layout { columns, kinds };
+        for filter in filters {
+            layout.kind(&filter.field)?;
+        }
+        return Ok(layout);
+    }
+
+    fn kind(&self, field: &str) -> Result<Kind, ExportError> {
+        return self.kinds.get(field).copied().ok_or_else(|| ExportError::UnknownField(field.to_string()));
+    }
+
+    //Arrow schema of the given columns, every one nullable
+    pub fn schema(&self, fields: &[String]) -> Result<Schema, ExportError> {
+        let fields = fields.iter()
+            .map(|field| Ok(Field::new(field.as_str(), self.kind(field)?.data_type(), true)))
+            .collect::<Result<Vec<Field>, ExportError>>()?;
+        return Ok(Schema::new(fields));
+    }
+}
+
+//Writes rows one at a time in an export format. Parquet rows are buffered and written batch_rows at a time
+enum RowWriter<W: Write + Send> {
+    Csv(csv::Writer<W>),
+    JsonLines(W, Vec<String>),
+    Parquet(ArrowWriter<W>, SchemaRef, Vec<Kind>, Vec<Vec<Value>>, usize)
+}
+
+impl<W: Write + Send> RowWriter<W> {
+    fn new(format: ExportFormat, layout: &Layout, columns: &[String], writer: W, batch_rows: usize) -> Result<Self, ExportError> {
+        let schema = Arc::new(layout.schema(columns)?);
+        return match format {
+            ExportFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record(columns)?;
+                Ok(RowWriter::Csv(writer))
+            },
+            ExportFormat::JsonLines => Ok(RowWriter::JsonLines(writer, columns.to_vec())),
+            ExportFormat::Parquet => {
+                let kinds = columns.iter().map(|column| layout.kind(column)).collect::<Result<Vec<Kind>, ExportError>>()?;
+                Ok(RowWriter::Parquet(ArrowWriter::try_new(writer, schema.clone(), None)?, schema, kinds, Vec::with_capacity(batch_rows), batch_rows))
+            }
+        };
+    }
+
+    fn write(&mut self, row: Vec<Value>) -> Result<(), ExportError> {
+        match self {
+            RowWriter::Csv(writer) => writer.write_record(row.iter().map(cell_text))?,
+            RowWriter::JsonLines(writer, columns) => {
+                let record: Map<String, Value> = columns.iter().cloned().zip(row).collect();
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            },
+            RowWriter::Parquet(writer, schema, kinds, rows, batch_rows) => {
+                rows.push(row);
+                if rows.len() >= *batch_rows {
+                    write_batch(writer, schema, kinds, rows)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn finish(self) -> Result<(), ExportError> {
+        match self {
+            RowWriter::Csv(mut writer) => writer.flush()?,
+            RowWriter::JsonLines(mut writer, _) => writer.flush()?,
+            RowWriter::Parquet(mut writer, schema, kinds, mut rows, _) => {
+                write_batch(&mut writer, &schema, &kinds, &mut rows)?;
+                writer.close()?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+//Writes the buffered rows as one record batch and empties the buffer
+fn write_batch<W: Write + Send>(writer: &mut ArrowWriter<W>, schema: &SchemaRef, kinds: &[Kind], rows: &mut Vec<Vec<Value>>) -> Result<(), ExportError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let arrays: Vec<ArrayRef> = kinds.iter().enumerate()
+        .map(|(i, kind)| kind.array(&rows.iter().map(|row| row[i].clone()).collect::<Vec<Value>>()))
+        .collect();
+    writer.write(&RecordBatch::try_new(schema.clone(), arrays)?)?;
+    rows.clear();
+    return Ok(());
+}
+
+//Flattened block, its metadata and then its payload's fields
+fn block_record<T: Payload>(block: &Block<T>) -> Result<Map<String, Value>, ExportError> {
+    let mut record = Map::new();
+    record.insert("block_id".to_string(), Value::from(block.id));
+    record.insert("hash".to_string(), Value::from(block.hash.clone()));
+    record.insert("previous_hash".to_string(), Value::from(block.previous_hash.clone()));
+    record.insert("timestamp".to_string(), Value::from(block.timestamp));
+    record.insert("schema_version".to_string(), Value::from(block.schema_version));
+    record.insert("hospital".to_string(), Value::from(block.hospital.clone()));
+    flatten_into("", serde_json::to_value(&block.payload)?, &mut record)?;
+    return Ok(record);
+}
+
+//Moves the fields of value into record, naming nested fields by their path. A field named like a column
+//already in the record is an error rather than overwriting it
+fn flatten_into(prefix: &str, value: Value, record: &mut Map<String, Value>) -> Result<(), ExportError> {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                let name = if prefix.is_empty() { key } else { format!("{}.{}", prefix, key) };
+                flatten_into(&name, field, record)?;
+            }
+        },
+        value => {
+            let name = if prefix.is_empty() { "payload" } else { prefix };
+            if record.contains_key(name) {
+                return Err(ExportError::ColumnCollision(name.to_string()));
+            }
+            record.insert(name.to_string(), value);
+        }
+    }
+    return Ok(());
+}
+
+//Cell as CSV text, strings unquoted and missing values empty
+fn cell_text(cell: &Value) -> String {
+    return match cell {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string()
+    };
+}
+
+//Filters, selects and writes the chain's blocks in options.format, returns the number of rows written.
+//Only one row at a time is held in memory, or one batch of BATCH_ROWS for Parquet
+pub fn export<T: Payload, W: Write + Send>(blockchain: &Blockchain<T>, options: &ExportOptions, writer: W) -> Result<usize, ExportError> {
+    return export_in_batches(blockchain, options, writer, BATCH_ROWS);
+}
+
+fn export_in_batches<T: Payload, W: Write + Send>(blockchain: &Blockchain<T>, options: &ExportOptions, writer: W, batch_rows: usize)
+    -> Result<usize, ExportError> {
+    let layout = Layout::scan(blockchain, &options.filters)?;
+    let columns = options.fields.clone().unwrap_or_else(|| layout.columns.clone());
+    let mut rows = RowWriter::new(options.format, &layout, &columns, writer, batch_rows)?;
+    let mut written = 0;
+    for block in blockchain.iter_blocks() {
+        let mut record = block_record(block)?;
+        if options.filters.iter().all(|filter| filter.matches(record.get(&filter.field).unwrap_or(&Value::Null))) {
+            rows.write(columns.iter().map(|column| record.remove(column).unwrap_or(Value::Null)).collect())?;
+            written += 1;
+        }
+    }
+    rows.finish()?;
+    return Ok(written);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::patient::Patient;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use serde::{Deserialize, Serialize};
+    use std::fs::File;
+
+    fn chain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        for (id, age, icu) in [("a", "40", "2"), ("b", "70", "1"), ("c", "65", "2")] {
+            blockchain.hospital = (id != "a").then(|| "north".to_string());
+            blockchain.add_patient_struct(Patient::from_columns(&[id, "1", "2", "10-05-2020", "08-05-2020", "9999-99-99", "2", "2", age,
+                "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "1", icu]).unwrap());
+        }
+        return blockchain;
+    }
+
+    #[test]
+    fn export_test() {
+        let blockchain = chain();
+        let mut options = ExportOptions::new(ExportFormat::Csv);
+        options.fields = Some(vec!["block_id".to_string(), "hospital".to_string(), "id".to_string(), "age".to_string(), "date_died".to_string()]);
+        let mut csv = Vec::new();
+        assert_eq!(export(&blockchain, &options, &mut csv).unwrap(), 3);
+        assert_eq!(String::from_utf8(csv).unwrap(), "block_id,hospital,id,age,date_died\n0,,a,40,\n1,north,b,70,\n2,north,c,65,\n");
+
+        options.filters = vec![Filter::parse("age>=60").unwrap()];
+        let mut csv = Vec::new();
+        assert_eq!(export(&blockchain, &options, &mut csv).unwrap(), 2);
+        assert_eq!(String::from_utf8(csv).unwrap(), "block_id,hospital,id,age,date_died\n1,north,b,70,\n2,north,c,65,\n");
+
+        options.format = ExportFormat::JsonLines;
+        options.filters.push(Filter::parse("icu = Yes").unwrap());
+        let mut lines = Vec::new();
+        assert_eq!(export(&blockchain, &options, &mut lines).unwrap(), 1);
+        let row: Value = serde_json::from_slice(&lines).unwrap();
+        assert_eq!(row["id"], "b");
+        assert_eq!(row["date_died"], Value::Null);
+
+        options.filters = vec![Filter::parse("ward=3").unwrap()];
+        assert!(matches!(export(&blockchain, &options, io::sink()), Err(ExportError::UnknownField(_))));
+        assert!(Filter::parse("age").is_err());
+    }
+
+    #[test]
+    fn parquet_test() {
+        let blockchain = chain();
+        let layout = Layout::scan(&blockchain, &[]).unwrap();
+        let schema = layout.schema(&layout.columns).unwrap();
+        assert_eq!(&layout.columns[..6], METADATA_COLUMNS);
+        assert_eq!(schema.field_with_name("age").unwrap().data_type(), &DataType::Int64);
+        assert_eq!(schema.field_with_name("entry_date").unwrap().data_type(), &DataType::Utf8);
+
+        //Batches of two rows, so the last batch is written when the export finishes
+        let path = std::env::temp_dir().join(format!("carle_chain_export_{}.parquet", std::process::id()));
+        let options = ExportOptions::new(ExportFormat::Parquet);
+        assert_eq!(export_in_batches(&blockchain, &options, File::create(&path).unwrap(), 2).unwrap(), 3);
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        assert_eq!(reader.metadata().file_metadata().schema_descr().num_columns(), layout.columns.len());
+    }
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Sample {
+        id: String,
+        hash: String
+    }
+
+    impl Payload for Sample {
+        type Error = String;
+
+        fn id(&self) -> &str {
+            return &self.id;
+        }
+
+        fn check(&self) -> Result<(), String> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn column_collision_test() {
+        let mut samples: Blockchain<Sample> = Blockchain::new();
+        samples.add_record(Sample { id: "s1".to_string(), hash: "abc".to_string() });
+        let result = export(&samples, &ExportOptions::new(ExportFormat::Csv), io::sink());
+        assert!(matches!(result, Err(ExportError::ColumnCollision(field)) if field == "hash"));
+    }
+}
@@ -0,0 +1,160 @@
+// Federated logistic regression: every hospital keeps its patient rows and only shares
+// the loss, gradient and Hessian of its own records at the coordinator's coefficients
+
+use ndarray::{Array1, Array2};
+use std::thread;
+
+use super::blockchain::Patient;
+use super::logreg::{fisher_information, invert, line_search, loss_gradient, quadratic_l1_step, result_from_information,
+    Convergence, RegressionError, RegressionResult, Solver, SolverOptions};
+use super::model::{training_data, Feature, Outcome};
+
+//Summary statistics a hospital shares with the coordinator, nothing in it identifies a single record
+#[derive(Debug, Clone)]
+pub struct LocalUpdate {
+    pub n_obs: usize,
+    pub loss: f64,
+    pub gradient: Array1<f64>,
+    pub hessian: Array2<f64>
+}
+
+//A hospital holding its own patients' features and outcomes
+#[derive(Debug)]
+pub struct HospitalNode {
+    pub name: String,
+    x: Array2<f64>,
+    y: Array1<f64>
+}
+
+impl HospitalNode {
+    pub fn new(name: String, x: Array2<f64>, y: Array1<f64>) -> Self {
+        return Self { name, x, y };
+    }
+
+    //Builds a node from the hospital's patients, predicting outcome from the given features
+    pub fn from_patients(name: String, patients: &[&Patient], features: &[Feature], outcome: Outcome) -> Self {
+        let (x, y) = training_data(patients.iter().copied(), features, outcome);
+        return Self::new(name, x, y);
+    }
+
+    pub fn n_obs(&self) -> usize {
+        return self.y.len();
+    }
+
+    //Loss, gradient and Hessian of this hospital's records at beta
+    pub fn local_update(&self, beta: &Array1<f64>) -> LocalUpdate {
+        let (loss, gradient) = loss_gradient(beta, &self.x, &self.y);
+        let hessian = fisher_information(beta, &self.x);
+        return LocalUpdate { n_obs: self.n_obs(), loss, gradient, hessian };
+    }
+}
+
+//Sums the hospitals' updates, which equals the update of the pooled records
+pub fn aggregate(updates: &[LocalUpdate]) -> Option<LocalUpdate> {
+    let mut iter = updates.iter();
+    let mut total = iter.next()?.clone();
+    for update in iter {
+        total.n_obs += update.n_obs;
+        total.loss += update.loss;
+        total.gradient += &update.gradient;
+        total.hessian += &update.hessian;
+    }
+    return Some(total);
+}
+
+//One communication round: each hospital computes its update on its own thread, the coordinator sums them
+fn round(nodes: &[HospitalNode], beta: &Array1<f64>) -> Option<LocalUpdate> {
+    let updates: Vec<LocalUpdate> = thread::scope(|scope| {
+        let handles: Vec<_> = nodes.iter()
+            .map(|node| scope.spawn(move || node.local_update(beta)))
+            .collect();
+        return handles.into_iter().map(|h| h.join().expect("hospital thread panicked")).collect();
+    });
+    return aggregate(&updates);
+}
+
+//Adds the penalty in options to an aggregated update, leaving the intercept unpenalized
+pub(crate) fn penalize(mut update: LocalUpdate, beta: &Array1<f64>, options: &SolverOptions) -> LocalUpdate {
+    let l2 = options.penalty.l2();
+    update.loss += options.penalty.value(beta);
+    for j in 1..beta.len() {
+        update.gradient[j] += l2 * beta[j];
+        update.hessian[[j, j]] += l2;
+    }
+    return update;
+}
+
+//Fits the pooled logistic regression by Newton-Raphson on aggregated updates. The solver in options is ignored,
+//gradient descent would need far more communication rounds for the same coefficients
+pub fn federated_regression(nodes: &[HospitalNode], names: &[String], options: &SolverOptions)
+    -> Result<RegressionResult, RegressionError> {
+    let n_features = nodes.first().ok_or(RegressionError::TooFewRows)?.x.ncols();
+    let n_obs: usize = nodes.iter().map(|node| node.n_obs()).sum();
+    if n_obs < n_features {
+        return Err(RegressionError::TooFewRows);
+    }
+
+    let mut beta = Array1::<f64>::zeros(n_features);
+    let mut current = penalize(round(nodes, &beta).expect("at least one node"), &beta, options);
+    let mut iterations = 0;
+    loop {
+        if iterations == options.max_iter {
+            return Err(RegressionError::NotConverged(options.max_iter));
+        }
+        iterations += 1;
+
+        let step = if options.penalty.l1() > 0.0 {
+            quadratic_l1_step(&beta, &current.gradient, &current.hessian, options.penalty.l1(), options)?
+        } else {
+            -invert(&current.hessian).ok_or(RegressionError::SingularHessian)?.dot(&current.gradient)
+        };
+
+        let (next_beta, next) = line_search(&beta, &step, current.loss, options.tol, |next_beta| {
+            let next = penalize(round(nodes, next_beta).expect("at least one node"), next_beta, options);
+            return (next.loss, next);
+        })?;
+
+        let max_change = (&next_beta - &beta).fold(0.0_f64, |m, d| m.max(d.abs()));
+        beta = next_beta;
+        current = next;
+        if max_change < options.tol {
+            break;
+        }
+    }
+
+    let unpenalized = round(nodes, &beta).expect("at least one node");
+    let gradient_norm = current.gradient.fold(0.0, |b, a| b + a * a).sqrt();
+    let mut result = result_from_information(beta, -unpenalized.loss, &unpenalized.hessian, n_obs, names);
+    result.convergence = Some(Convergence { solver: Solver::Newton, iterations, gradient_norm });
+    if options.penalty.is_penalized() {
+        result.suppress_inference();
+    }
+    return Ok(result);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::logreg::logistic_regression;
+    use ndarray::{array, s};
+
+    #[test]
+    fn federated_matches_centralized_test() {
+        let x = array![[1.0, 0.0, 1.0], [1.0, 1.0, 0.0], [1.0, 2.0, 1.0], [1.0, 3.0, 0.0],
+            [1.0, 4.0, 1.0], [1.0, 5.0, 0.0], [1.0, 1.5, 1.0], [1.0, 2.5, 0.0]];
+        let y = array![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0];
+        let names = vec!["intercept".to_string(), "a".to_string(), "b".to_string()];
+        let nodes = vec![
+            HospitalNode::new("north".to_string(), x.slice(s![..3, ..]).to_owned(), y.slice(s![..3]).to_owned()),
+            HospitalNode::new("south".to_string(), x.slice(s![3.., ..]).to_owned(), y.slice(s![3..]).to_owned())
+        ];
+        let options = SolverOptions::default();
+        let federated = federated_regression(&nodes, &names, &options).unwrap();
+        let centralized = logistic_regression(&x, &y, &names, &options).unwrap();
+        for i in 0..3 {
+            assert!((federated.coefficients[i] - centralized.coefficients[i]).abs() < 1e-6);
+            assert!((federated.std_errors[i] - centralized.std_errors[i]).abs() < 1e-6);
+        }
+        assert_eq!(federated.n_obs, 8);
+    }
+}
@@ -0,0 +1,620 @@
+// Import of HL7 FHIR JSON bundles, the format hospitals exchange records in. Patient, Condition, Observation
+// and Encounter resources are mapped onto the dataset's Patient fields:
+//
+//   Patient      gender, birthDate (age at admission, a year or month only is taken as its middle) and a
+//                deceasedDateTime within the episode
+//   Encounter    earliest period.start in the episode is the entry date, an inpatient class (IMP, ACUTE, NONAC) makes a
+//                hospitalized patient and an intensive care type, service or location an ICU stay
+//   Condition    confirmed SNOMED CT or ICD-10 coded comorbidities that are active, COVID-19 diagnoses and their
+//                onset. Other codes are listed in the report and other_disease is left unknown
+//   Observation  SARS-CoV-2 test results, smoking status and pregnancy status by LOINC code
+//
+// A bundle can hold a patient's whole history, so the record describes one COVID-19 episode: the days around the
+// first positive SARS-CoV-2 test or COVID-19 diagnosis, or the first test when none is positive. Encounters and
+// tests outside it are not used, nor is a death after it.
+//
+// Anything else, or a resource whose subject is not a Patient in the bundle, is listed in the MappingReport.
+
+use chrono::{Days, NaiveDate};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use super::model::Feature;
+use super::patient::{CovidResult, Flag, Patient, PatientType, Sex};
+use super::survival::parse_date;
+
+const SNOMED: &str = "http://snomed.info/sct";
+const LOINC: &str = "http://loinc.org";
+
+//Comorbidities by SNOMED CT code and ICD-10 code prefix
+const CONDITION_CODES: [(Feature, &[&str], &[&str]); 11] = [
+    (Feature::Pneumonia, &["233604007", "882784691000119100"], &["J12", "J13", "J14", "J15", "J16", "J17", "J18"]),
+    (Feature::Pregnancy, &["77386006"], &["Z33", "Z34"]),
+    (Feature::Diabetes, &["73211009", "44054006", "46635009"], &["E10", "E11", "E13", "E14"]),
+    (Feature::Copd, &["13645005"], &["J44"]),
+    (Feature::Asthma, &["195967001"], &["J45"]),
+    (Feature::Immunosuppressed, &["38013005", "234532001"], &["D84", "D89"]),
+    (Feature::Hypertension, &["38341003", "59621000"], &["I10", "I11", "I12", "I13", "I15"]),
+    (Feature::Cardiovascular, &["49601007", "53741008", "84114007"], &["I20", "I21", "I22", "I25", "I50"]),
+    (Feature::Obesity, &["414916001", "238136002"], &["E66"]),
+    (Feature::RenalChronic, &["709044004", "431855005"], &["N18"]),
+    (Feature::Tobacco, &["77176002", "449868002"], &["F17", "Z72.0"])
+];
+
+//COVID-19 diagnosis, SNOMED CT and ICD-10
+const COVID_CONDITION: (&str, &str) = ("840539006", "U07.1");
+
+//SARS-CoV-2 RNA and antigen tests
+const COVID_TESTS: [&str; 8] = ["94500-6", "94309-2", "94533-7", "94534-5", "94558-4", "94559-2", "94745-7", "94746-5"];
+const SMOKING_STATUS: &str = "72166-2";
+const PREGNANCY_STATUS: &str = "82810-3";
+
+//Days of the COVID-19 episode before and after its first positive test or diagnosis
+const EPISODE_DAYS_BEFORE: u64 = 14;
+const EPISODE_DAYS_AFTER: u64 = 28;
+//Days before the episode a pregnancy can have been recorded in
+const PREGNANCY_DAYS: u64 = 280;
+const OUTSIDE_EPISODE: &str = "dated outside the COVID-19 episode";
+const DEATH_OUTSIDE_EPISODE: &str = "deceasedDateTime is outside the COVID-19 episode, the patient is recorded as alive";
+
+#[derive(Debug)]
+//Enum describing why a bundle could not be read
+pub enum FhirError {
+    Json(serde_json::Error),
+    NotABundle(String)
+}
+
+impl fmt::Display for FhirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            FhirError::Json(err) => write!(f, "bundle is not valid JSON: {}", err),
+            FhirError::NotABundle(resource_type) => write!(f, "expected a FHIR Bundle, found {:?}", resource_type)
+        };
+    }
+}
+
+impl Error for FhirError {}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+//Resource that did not contribute to a patient
+pub struct Unmapped {
+    pub resource_type: String,
+    pub id: String,
+    pub reason: String
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, Default)]
+//What became of each resource of a bundle
+pub struct MappingReport {
+    pub resources: usize,
+    //Resources mapped onto a patient, by resource type
+    pub mapped: BTreeMap<String, usize>,
+    pub unmapped: Vec<Unmapped>,
+    //Patient resources no record could be made from
+    pub skipped_patients: Vec<Unmapped>,
+    //Blocks the records became, set when the bundle is imported into a chain
+    pub added: usize
+}
+
+impl fmt::Display for MappingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "read {} resources, added {} blocks", self.resources, self.added)?;
+        for (resource_type, count) in &self.mapped {
+            writeln!(f, "mapped {} {}", count, resource_type)?;
+        }
+        for skipped in &self.skipped_patients {
+            writeln!(f, "skipped Patient/{}: {}", skipped.id, skipped.reason)?;
+        }
+        for unmapped in &self.unmapped {
+            writeln!(f, "unmapped {}/{}: {}", unmapped.resource_type, unmapped.id, unmapped.reason)?;
+        }
+        return Ok(());
+    }
+}
+
+impl MappingReport {
+    fn unmapped(&mut self, resource: &Value, reason: impl ToString) {
+        self.unmapped.push(Unmapped { resource_type: text(&resource["resourceType"]), id: text(&resource["id"]), reason: reason.to_string() });
+    }
+
+    //Counts a resource as mapped, or lists it as unmapped for reason
+    fn account(&mut self, source: &Source, reason: Option<&str>) {
+        match reason {
+            None => *self.mapped.entry(source.resource_type.clone()).or_default() += 1,
+            Some(reason) => self.unmapped.push(Unmapped { resource_type: source.resource_type.clone(), id: source.id.clone(), reason: reason.to_string() })
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+//Resource a fact was read from
+struct Source {
+    resource_type: String,
+    id: String
+}
+
+#[derive(Debug)]
+//Something a resource says about the patient, dated when the resource is
+struct Fact<V> {
+    source: Source,
+    date: Option<NaiveDate>,
+    value: V
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Stay {
+    admitted: bool,
+    icu: bool
+}
+
+//Resources a patient was built from, with the reason a resource went unused
+type Uses<'a> = Vec<(&'a Source, Option<&'static str>)>;
+
+#[derive(Debug, Default)]
+//Everything the bundle says about one patient, turned into a Patient once every resource is read
+struct Draft {
+    sex: Option<Sex>,
+    birth_date: Option<NaiveDate>,
+    //deceasedDateTime of the Patient resource
+    death: Option<Fact<()>>,
+    //Encounters dated by their start
+    encounters: Vec<Fact<Stay>>,
+    tests: Vec<Fact<CovidResult>>,
+    //COVID-19 diagnoses dated by their onset
+    diagnoses: Vec<Fact<()>>,
+    conditions: Vec<Fact<Feature>>,
+    smoking: Vec<Fact<bool>>,
+    pregnancy: Vec<Fact<bool>>
+}
+
+//Facts select keeps, noting the others as unused for reason
+fn select<'a, V>(facts: &'a [Fact<V>], keep: impl Fn(&Fact<V>) -> bool, reason: &'static str, uses: &mut Uses<'a>) -> Vec<&'a Fact<V>> {
+    let mut kept = Vec::new();
+    for fact in facts {
+        let used = keep(fact);
+        uses.push((&fact.source, (!used).then_some(reason)));
+        if used {
+            kept.push(fact);
+        }
+    }
+    return kept;
+}
+
+//Value of the most recently dated fact, undated facts count as the oldest
+fn latest<V: Copy>(facts: &[&Fact<V>]) -> Option<V> {
+    return facts.iter().max_by_key(|fact| fact.date).map(|fact| fact.value);
+}
+
+impl Draft {
+    fn sources(&self) -> Vec<&Source> {
+        let mut sources: Vec<&Source> = self.encounters.iter().map(|fact| &fact.source).collect();
+        sources.extend(self.tests.iter().map(|fact| &fact.source));
+        sources.extend(self.diagnoses.iter().map(|fact| &fact.source));
+        sources.extend(self.conditions.iter().map(|fact| &fact.source));
+        sources.extend(self.smoking.iter().map(|fact| &fact.source));
+        sources.extend(self.pregnancy.iter().map(|fact| &fact.source));
+        return sources;
+    }
+
+    //The patient's COVID-19 episode, anchored on the first positive test or diagnosis, or on the first test when none
+    //is positive. Encounters, tests and diagnoses count within the episode, comorbidities and smoking status up to its
+    //end and pregnancy from PREGNANCY_DAYS before it
+    fn to_patient(&self, id: &str) -> Result<(Patient, Uses<'_>), String> {
+        let sex = self.sex.ok_or("gender is not male or female")?;
+        let birth_date = self.birth_date.ok_or("no birthDate")?;
+        let positives = self.tests.iter().filter(|test| test.value == CovidResult::Positive).filter_map(|test| test.date)
+            .chain(self.diagnoses.iter().filter_map(|diagnosis| diagnosis.date));
+        let anchor = positives.min().or_else(|| self.tests.iter().filter_map(|test| test.date).min())
+            .ok_or("no dated SARS-CoV-2 test or COVID-19 diagnosis to anchor the episode on")?;
+        let (first, last) = (anchor - Days::new(EPISODE_DAYS_BEFORE), anchor + Days::new(EPISODE_DAYS_AFTER));
+        let pregnancy_start = anchor - Days::new(PREGNANCY_DAYS);
+        let during = |date: Option<NaiveDate>| date.is_some_and(|date| first <= date && date <= last);
+        let until_end = |date: Option<NaiveDate>| date.is_none_or(|date| date <= last);
+        let pregnant_during = |date: Option<NaiveDate>| date.is_none_or(|date| pregnancy_start <= date && date <= last);
+
+        let mut uses: Uses = Vec::new();
+        let encounters = select(&self.encounters, |fact| during(fact.date), OUTSIDE_EPISODE, &mut uses);
+        let tests = select(&self.tests, |fact| during(fact.date), OUTSIDE_EPISODE, &mut uses);
+        let diagnoses = select(&self.diagnoses, |fact| during(fact.date), OUTSIDE_EPISODE, &mut uses);
+        let conditions = select(&self.conditions, |fact| {
+            return if fact.value == Feature::Pregnancy { pregnant_during(fact.date) } else { until_end(fact.date) };
+        }, OUTSIDE_EPISODE, &mut uses);
+        let smoking = select(&self.smoking, |fact| until_end(fact.date), OUTSIDE_EPISODE, &mut uses);
+        let pregnancy = select(&self.pregnancy, |fact| pregnant_during(fact.date), OUTSIDE_EPISODE, &mut uses);
+
+        let entry_date = encounters.iter().filter_map(|fact| fact.date).min().unwrap_or(anchor);
+        let age = entry_date.years_since(birth_date).ok_or("birthDate is after the entry date")?;
+        let inpatient = encounters.iter().any(|fact| fact.value.admitted);
+        let icu = encounters.iter().any(|fact| fact.value.admitted && fact.value.icu);
+        let covid_res = if !diagnoses.is_empty() || tests.iter().any(|fact| fact.value == CovidResult::Positive) {
+            CovidResult::Positive
+        } else if tests.iter().any(|fact| fact.value == CovidResult::Negative) {
+            CovidResult::Negative
+        } else {
+            CovidResult::Pending
+        };
+        let has = |feature: Feature| if conditions.iter().any(|fact| fact.value == feature) { Flag::Yes } else { Flag::No };
+        let pregnancy = match (has(Feature::Pregnancy), latest(&pregnancy)) {
+            (Flag::Yes, _) | (_, Some(true)) => Flag::Yes,
+            (_, Some(false)) => Flag::No,
+            //Not asked, as the dataset's not applicable code for men
+            (_, None) => Flag::Unknown
+        };
+        let tobacco = if latest(&smoking) == Some(true) { Flag::Yes } else { has(Feature::Tobacco) };
+        let date_died = match &self.death {
+            Some(death) if during(death.date) => death.date,
+            Some(death) => {
+                uses.push((&death.source, Some(DEATH_OUTSIDE_EPISODE)));
+                None
+            },
+            None => None
+        };
+        let patient = Patient {
+            id: id.to_string(),
+            sex,
+            patient_type: if inpatient { PatientType::Inpatient } else { PatientType::Outpatient },
+            entry_date,
+            date_symptoms: diagnoses.iter().filter_map(|fact| fact.date).min().filter(|onset| *onset <= entry_date).unwrap_or(entry_date),
+            date_died,
+            //Ventilation is recorded as a Procedure, which is not mapped
+            intubed: Flag::Unknown,
+            pneumonia: has(Feature::Pneumonia),
+            age,
+            pregnancy,
+            diabetes: has(Feature::Diabetes),
+            copd: has(Feature::Copd),
+            asthma: has(Feature::Asthma),
+            inmsupr: has(Feature::Immunosuppressed),
+            hypertension: has(Feature::Hypertension),
+            //Which other diseases the dataset counted is not known, the unmatched codes are in the report instead
+            other_disease: Flag::Unknown,
+            cardiovascular: has(Feature::Cardiovascular),
+            obesity: has(Feature::Obesity),
+            renal_chronic: has(Feature::RenalChronic),
+            tobacco,
+            contact_other_covid: Flag::Unknown,
+            covid_res,
+            //Not applicable to patients who were not admitted, as in the dataset
+            icu: if !inpatient { Flag::Unknown } else if icu { Flag::Yes } else { Flag::No }
+        };
+        return Ok((patient, uses));
+    }
+}
+
+fn text(value: &Value) -> String {
+    return value.as_str().unwrap_or("").to_string();
+}
+
+//Date part of a FHIR date or dateTime
+fn fhir_date(value: &Value) -> Option<NaiveDate> {
+    return value.as_str().and_then(|date| date.get(..10)).and_then(parse_date);
+}
+
+//A FHIR date that can be a year or a year and month only, taken as the middle of it
+fn fhir_partial_date(value: &Value) -> Option<NaiveDate> {
+    let date = value.as_str()?;
+    let parts: Vec<&str> = date.split('-').collect();
+    return match parts[..] {
+        [year] if year.len() == 4 => NaiveDate::from_ymd_opt(year.parse().ok()?, 7, 1),
+        [year, month] if year.len() == 4 && month.len() == 2 => NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 15),
+        _ => fhir_date(value)
+    };
+}
+
+fn coding(coding: &Value) -> (&str, &str) {
+    return (coding["system"].as_str().unwrap_or(""), coding["code"].as_str().unwrap_or(""));
+}
+
+//(system, code) of every coding of a CodeableConcept, or of a bare Coding
+fn codings(concept: &Value) -> Vec<(&str, &str)> {
+    return match concept["coding"].as_array() {
+        Some(list) => list.iter().map(coding).collect(),
+        None if concept["code"].is_string() => vec![coding(concept)],
+        None => vec![]
+    };
+}
+
+fn has_code(concept: &Value, system: &str, codes: &[&str]) -> bool {
+    return codings(concept).iter().any(|(s, code)| *s == system && codes.contains(code));
+}
+
+fn is_icd10(system: &str) -> bool {
+    return system.contains("icd-10");
+}
+
+//Text of a concept and its codings' displays, lower case
+fn concept_text(concept: &Value) -> String {
+    let mut words = text(&concept["text"]);
+    for coding in concept["coding"].as_array().into_iter().flatten() {
+        words.push(' ');
+        words.push_str(&text(&coding["display"]));
+    }
+    words.push(' ');
+    words.push_str(&text(&concept["display"]));
+    return words.to_lowercase();
+}
+
+fn mentions_intensive_care(concept: &Value) -> bool {
+    let words = concept_text(concept);
+    return has_code(concept, SNOMED, &["309904001"]) || words.contains("intensive care") || words.split(|c: char| !c.is_alphanumeric()).any(|word| word == "icu");
+}
+
+fn map_patient(draft: &mut Draft, resource: &Value, report: &mut MappingReport) {
+    draft.sex = match resource["gender"].as_str() {
+        Some("female") => Some(Sex::Female),
+        Some("male") => Some(Sex::Male),
+        _ => None
+    };
+    draft.birth_date = fhir_partial_date(&resource["birthDate"]);
+    let date_died = fhir_date(&resource["deceasedDateTime"]);
+    if resource["deceasedDateTime"].is_string() && date_died.is_none() {
+        report.unmapped(resource, "deceasedDateTime is not a full date, the patient is recorded as alive");
+    }
+    if resource["deceasedBoolean"] == Value::Bool(true) {
+        report.unmapped(resource, "deceasedBoolean has no date, the patient is recorded as alive");
+    }
+    draft.death = date_died.map(|date| Fact { source: source(resource), date: Some(date), value: () });
+}
+
+fn source(resource: &Value) -> Source {
+    return Source { resource_type: text(&resource["resourceType"]), id: text(&resource["id"]) };
+}
+
+fn map_encounter(draft: &mut Draft, resource: &Value) -> Result<(), String> {
+    let start = fhir_date(&resource["period"]["start"]).or_else(|| fhir_date(&resource["actualPeriod"]["start"]))
+        .ok_or("no period start")?;
+    //class is a Coding up to R4 and a list of CodeableConcepts from R5
+    let classes: Vec<&str> = match resource["class"].as_array() {
+        Some(list) => list.iter().flat_map(codings).map(|(_, code)| code).collect(),
+        None => codings(&resource["class"]).into_iter().map(|(_, code)| code).collect()
+    };
+    let admitted = classes.iter().any(|code| ["IMP", "ACUTE", "NONAC"].contains(code));
+    let mut places = resource["type"].as_array().cloned().unwrap_or_default();
+    places.push(resource["serviceType"].clone());
+    places.extend(resource["location"].as_array().into_iter().flatten().map(|location| location["location"].clone()));
+    let icu = places.iter().any(mentions_intensive_care);
+    draft.encounters.push(Fact { source: source(resource), date: Some(start), value: Stay { admitted, icu } });
+    return Ok(());
+}
+
+//Code of a status element, which is absent from many records
+fn status(concept: &Value) -> Option<&str> {
+    return codings(concept).first().map(|(_, code)| *code);
+}
+
+fn map_condition(draft: &mut Draft, resource: &Value) -> Result<(), String> {
+    if let Some(verification) = status(&resource["verificationStatus"]).filter(|code| *code != "confirmed") {
+        return Err(format!("condition is not confirmed ({})", verification));
+    }
+    let code = &resource["code"];
+    let coded = codings(code);
+    if coded.is_empty() {
+        return Err("condition has no coding".to_string());
+    }
+    let date = fhir_date(&resource["onsetDateTime"]).or(fhir_date(&resource["onsetPeriod"]["start"])).or(fhir_date(&resource["recordedDate"]));
+    //A resolved COVID-19 diagnosis still dates the episode
+    if coded.iter().any(|(system, c)| (*system == SNOMED && *c == COVID_CONDITION.0) || (is_icd10(system) && *c == COVID_CONDITION.1)) {
+        draft.diagnoses.push(Fact { source: source(resource), date, value: () });
+        return Ok(());
+    }
+    if let Some(clinical) = status(&resource["clinicalStatus"]).filter(|code| !["active", "recurrence", "relapse"].contains(code)) {
+        return Err(format!("condition is not active ({})", clinical));
+    }
+    let matched = CONDITION_CODES.iter().find(|(_, snomed, icd10)| coded.iter().any(|(system, c)| {
+        return (*system == SNOMED && snomed.contains(c)) || (is_icd10(system) && icd10.iter().any(|prefix| c.starts_with(prefix)));
+    }));
+    match matched {
+        Some((feature, _, _)) => {
+            draft.conditions.push(Fact { source: source(resource), date, value: *feature });
+            return Ok(());
+        },
+        None => {
+            let codes: Vec<String> = coded.iter().map(|(system, c)| format!("{}|{}", system, c)).collect();
+            return Err(format!("no dataset field for condition code {}", codes.join(", ")));
+        }
+    }
+}
+
+fn map_observation(draft: &mut Draft, resource: &Value) -> Result<(), String> {
+    let code = &resource["code"];
+    let value = &resource["valueCodeableConcept"];
+    let date = fhir_date(&resource["effectiveDateTime"]).or(fhir_date(&resource["effectivePeriod"]["start"])).or(fhir_date(&resource["issued"]));
+    if has_code(code, LOINC, &COVID_TESTS) {
+        let words = format!("{} {}", concept_text(value), text(&resource["valueString"]).to_lowercase());
+        let result = if has_code(value, SNOMED, &["260373001", "10828004"]) || words.contains("positive") || (words.contains("detected") && !words.contains("not detected")) {
+            CovidResult::Positive
+        } else if has_code(value, SNOMED, &["260415000", "260385009"]) || words.contains("negative") || words.contains("not detected") {
+            CovidResult::Negative
+        } else {
+            CovidResult::Pending
+        };
+        draft.tests.push(Fact { source: source(resource), date, value: result });
+    } else if has_code(code, LOINC, &[SMOKING_STATUS]) {
+        if has_code(value, SNOMED, &["449868002", "428041000124106", "77176002", "428071000124103", "428061000124105"]) {
+            draft.smoking.push(Fact { source: source(resource), date, value: true });
+        } else if has_code(value, SNOMED, &["8517006", "266919005"]) {
+            draft.smoking.push(Fact { source: source(resource), date, value: false });
+        } else {
+            return Err("smoking status value not mapped".to_string());
+        }
+    } else if has_code(code, LOINC, &[PREGNANCY_STATUS]) {
+        if has_code(value, SNOMED, &["77386006"]) {
+            draft.pregnancy.push(Fact { source: source(resource), date, value: true });
+        } else if has_code(value, SNOMED, &["60001007"]) {
+            draft.pregnancy.push(Fact { source: source(resource), date, value: false });
+        } else {
+            return Err("pregnancy status value not mapped".to_string());
+        }
+    } else {
+        return Err(format!("observation code {:?} not mapped", codings(code).first().map(|(_, c)| *c).unwrap_or("")));
+    }
+    return Ok(());
+}
+
+//Maps a FHIR Bundle in JSON onto patients, in the order their Patient resources appear
+pub fn read_bundle(json: &str) -> Result<(Vec<Patient>, MappingReport), FhirError> {
+    let bundle: Value = serde_json::from_str(json).map_err(FhirError::Json)?;
+    if bundle["resourceType"] != "Bundle" {
+        return Err(FhirError::NotABundle(text(&bundle["resourceType"])));
+    }
+    let entries = bundle["entry"].as_array().cloned().unwrap_or_default();
+    let mut report = MappingReport { resources: entries.len(), ..MappingReport::default() };
+
+    //References a resource may use for each patient: Patient/{id} and the entry's fullUrl
+    let mut order: Vec<String> = Vec::new();
+    let mut references: HashMap<String, String> = HashMap::new();
+    let mut drafts: HashMap<String, Draft> = HashMap::new();
+    for entry in &entries {
+        let resource = &entry["resource"];
+        if resource["resourceType"] == "Patient" {
+            let id = text(&resource["id"]);
+            references.insert(format!("Patient/{}", id), id.clone());
+            if let Some(url) = entry["fullUrl"].as_str() {
+                references.insert(url.to_string(), id.clone());
+            }
+            let mut draft = Draft::default();
+            map_patient(&mut draft, resource, &mut report);
+            drafts.insert(id.clone(), draft);
+            order.push(id);
+        }
+    }
+
+    for entry in &entries {
+        let resource = &entry["resource"];
+        let resource_type = text(&resource["resourceType"]);
+        if resource_type == "Patient" {
+            continue;
+        }
+        if !["Condition", "Observation", "Encounter"].contains(&resource_type.as_str()) {
+            report.unmapped(resource, "resource type not mapped");
+            continue;
+        }
+        let reference = text(&resource["subject"]["reference"]);
+        //Absolute references end in Patient/{id}
+        let patient = references.get(&reference)
+            .or_else(|| reference.rfind("Patient/").and_then(|i| references.get(&reference[i..])));
+        let draft = match patient.and_then(|id| drafts.get_mut(id)) {
+            Some(draft) => draft,
+            None => {
+                report.unmapped(resource, format!("subject {:?} is not a Patient in the bundle", reference));
+                continue;
+            }
+        };
+        let mapped = match resource_type.as_str() {
+            "Condition" => map_condition(draft, resource),
+            "Observation" => map_observation(draft, resource),
+            _ => map_encounter(draft, resource)
+        };
+        if let Err(reason) = mapped {
+            report.unmapped(resource, reason);
+        }
+    }
+
+    //Resources are counted once it is known whether their patient's record could be made
+    let mut patients: Vec<Patient> = Vec::new();
+    for id in order {
+        let draft = drafts.remove(&id).unwrap_or_default();
+        let made = draft.to_patient(&id).and_then(|(patient, uses)| {
+            return patient.validate().map(|_| (patient, uses)).map_err(|err| err.to_string());
+        });
+        match made {
+            Ok((patient, uses)) => {
+                report.account(&Source { resource_type: "Patient".to_string(), id: id.clone() }, None);
+                for (source, reason) in uses {
+                    report.account(source, reason);
+                }
+                patients.push(patient);
+            },
+            Err(reason) => {
+                for source in draft.sources() {
+                    report.account(source, Some("its patient was skipped"));
+                }
+                report.skipped_patients.push(Unmapped { resource_type: "Patient".to_string(), id, reason });
+            }
+        }
+    }
+    return Ok((patients, report));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn bundle() -> String {
+        let entry = |resource: Value| json!({ "resource": resource });
+        return json!({ "resourceType": "Bundle", "type": "collection", "entry": [
+            { "fullUrl": "urn:uuid:p1", "resource": { "resourceType": "Patient", "id": "p1", "gender": "male", "birthDate": "1950-07-01",
+                "deceasedDateTime": "2020-05-20T10:00:00Z" } },
+            entry(json!({ "resourceType": "Patient", "id": "p2", "gender": "female", "birthDate": "1990-03",
+                "deceasedDateTime": "2023-01-09" })),
+            entry(json!({ "resourceType": "Patient", "id": "p3", "gender": "unknown", "birthDate": "1980-01-01" })),
+            entry(json!({ "resourceType": "Encounter", "id": "e1", "subject": { "reference": "urn:uuid:p1" },
+                "class": { "system": "http://terminology.hl7.org/CodeSystem/v3-ActCode", "code": "IMP" },
+                "period": { "start": "2020-05-10T08:00:00Z" }, "location": [{ "location": { "display": "Ward 3 ICU" } }] })),
+            entry(json!({ "resourceType": "Condition", "id": "c1", "subject": { "reference": "Patient/p1" },
+                "code": { "coding": [{ "system": "http://hl7.org/fhir/sid/icd-10", "code": "E11.9" }] } })),
+            entry(json!({ "resourceType": "Condition", "id": "c2", "subject": { "reference": "Patient/p1" },
+                "code": { "coding": [{ "system": SNOMED, "code": "840539006" }] }, "onsetDateTime": "2020-05-07" })),
+            entry(json!({ "resourceType": "Condition", "id": "c3", "subject": { "reference": "Patient/p1" },
+                "code": { "coding": [{ "system": SNOMED, "code": "35489007" }] } })),
+            entry(json!({ "resourceType": "Condition", "id": "c5", "subject": { "reference": "Patient/p1" },
+                "clinicalStatus": { "coding": [{ "code": "resolved" }] },
+                "code": { "coding": [{ "system": SNOMED, "code": "38341003" }] } })),
+            entry(json!({ "resourceType": "Condition", "id": "c6", "subject": { "reference": "Patient/p1" },
+                "verificationStatus": { "coding": [{ "code": "provisional" }] },
+                "code": { "coding": [{ "system": SNOMED, "code": "13645005" }] } })),
+            entry(json!({ "resourceType": "Encounter", "id": "e2", "subject": { "reference": "Patient/p2" },
+                "class": { "code": "AMB" }, "period": { "start": "2020-06-01" } })),
+            entry(json!({ "resourceType": "Encounter", "id": "e3", "subject": { "reference": "Patient/p2" },
+                "class": { "code": "IMP" }, "period": { "start": "2015-03-02" }, "serviceType": { "text": "Intensive care" } })),
+            entry(json!({ "resourceType": "Observation", "id": "o1", "subject": { "reference": "Patient/p2" },
+                "code": { "coding": [{ "system": LOINC, "code": "94500-6" }] }, "effectiveDateTime": "2020-06-01",
+                "valueCodeableConcept": { "coding": [{ "system": SNOMED, "code": "260415000" }] } })),
+            entry(json!({ "resourceType": "Observation", "id": "o2", "subject": { "reference": "Patient/p2" },
+                "code": { "coding": [{ "system": LOINC, "code": PREGNANCY_STATUS }] },
+                "valueCodeableConcept": { "coding": [{ "system": SNOMED, "code": "77386006" }] } })),
+            entry(json!({ "resourceType": "Observation", "id": "o3", "subject": { "reference": "Patient/p2" },
+                "code": { "coding": [{ "system": LOINC, "code": "8867-4" }] }, "valueQuantity": { "value": 72 } })),
+            entry(json!({ "resourceType": "Condition", "id": "c4", "subject": { "reference": "Patient/p9" },
+                "code": { "coding": [{ "system": SNOMED, "code": "38341003" }] } })),
+            entry(json!({ "resourceType": "MedicationRequest", "id": "m1", "subject": { "reference": "Patient/p1" } }))
+        ] }).to_string();
+    }
+
+    #[test]
+    fn read_bundle_test() {
+        let (patients, report) = read_bundle(&bundle()).unwrap();
+        assert_eq!(patients.len(), 2);
+        let p1 = &patients[0];
+        assert_eq!((p1.sex, p1.patient_type, p1.age), (Sex::Male, PatientType::Inpatient, 69));
+        assert_eq!(p1.entry_date, NaiveDate::from_ymd_opt(2020, 5, 10).unwrap());
+        assert_eq!(p1.date_symptoms, NaiveDate::from_ymd_opt(2020, 5, 7).unwrap());
+        assert_eq!(p1.date_died, NaiveDate::from_ymd_opt(2020, 5, 20));
+        assert_eq!((p1.icu, p1.diabetes, p1.other_disease, p1.hypertension, p1.copd), (Flag::Yes, Flag::Yes, Flag::Unknown, Flag::No, Flag::No));
+        assert_eq!(p1.covid_res, CovidResult::Positive);
+        let p2 = &patients[1];
+        assert_eq!((p2.patient_type, p2.icu, p2.pregnancy, p2.covid_res), (PatientType::Outpatient, Flag::Unknown, Flag::Yes, CovidResult::Negative));
+        //Born mid March 1990, and the 2023 death is not part of the episode
+        assert_eq!((p2.age, p2.date_died), (30, None));
+
+        //The 2015 admission of p2 is not part of the episode
+        assert_eq!(p2.entry_date, NaiveDate::from_ymd_opt(2020, 6, 1).unwrap());
+
+        assert_eq!(report.resources, 16);
+        assert_eq!(report.mapped.get("Condition"), Some(&2));
+        assert_eq!(report.mapped.get("Encounter"), Some(&2));
+        assert_eq!(report.skipped_patients.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(), ["p3"]);
+        assert_eq!(report.unmapped.iter().map(|u| u.id.as_str()).collect::<Vec<&str>>(), ["c3", "c5", "c6", "o3", "c4", "m1", "e3", "p2"]);
+        assert_eq!(report.unmapped[0].reason, format!("no dataset field for condition code {}|35489007", SNOMED));
+        assert_eq!(report.unmapped[6].reason, OUTSIDE_EPISODE);
+        assert_eq!(report.unmapped[7].reason, DEATH_OUTSIDE_EPISODE);
+        assert_eq!(fhir_partial_date(&json!("1950")), NaiveDate::from_ymd_opt(1950, 7, 1));
+        assert_eq!(fhir_partial_date(&json!("1950-04")), NaiveDate::from_ymd_opt(1950, 4, 15));
+        assert_eq!(fhir_partial_date(&json!("1950-04-02")), NaiveDate::from_ymd_opt(1950, 4, 2));
+        assert!(matches!(read_bundle("{\"resourceType\": \"Patient\"}"), Err(FhirError::NotABundle(_))));
+    }
+}
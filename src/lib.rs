@@ -0,0 +1,43 @@
+//! CarleChain: a proof-of-work blockchain of hospital patient records with analytics run directly on the chain.
+//!
+//! - [`blockchain`]: [`Blockchain`] and [`Block`], generic over any [`Payload`] record, CSV ingestion and chain validation
+//! - [`patient`]: typed [`Patient`] records parsed and checked from the dataset's codes
+//! - [`logreg`], [`analytics`], [`classifier`]: model fitting and evaluation on the chain's records
+//! - [`model`]: fitted models that score patients, persist as JSON and can be anchored on the chain
+//! - [`online`], [`federated`], [`privacy`], [`survival`]: incremental, distributed, differentially private and time-to-event analyses
+//! - [`mempool`]: queue of submitted patients mined into blocks by a background producer
+//! - [`api`]: local HTTP/JSON server over a shared chain
+//! - [`export`]: blocks flattened to CSV, JSON Lines or Parquet, with field selection and filters
+//! - [`fhir`]: import of HL7 FHIR bundles onto patients, with a report of the resources left unmapped
+//!
+//! ```no_run
+//! use carle_chain::{Blockchain, Outcome, SolverOptions};
+//!
+//! let mut blockchain = Blockchain::new();
+//! blockchain.csv_to_blockchain_range(&"data/covid.csv".to_string(), 0, 50).unwrap();
+//! assert!(blockchain.validate_chain());
+//! println!("{}", blockchain.run_regression(Outcome::Died, &SolverOptions::default()).unwrap());
+//! ```
+
+// The codebase returns explicitly from every function
+#![allow(clippy::needless_return)]
+
+pub mod analytics;
+pub mod api;
+pub mod blockchain;
+pub mod classifier;
+pub mod export;
+pub mod federated;
+pub mod fhir;
+pub mod logreg;
+pub mod mempool;
+pub mod model;
+pub mod online;
+pub mod patient;
+pub mod privacy;
+pub mod survival;
+
+pub use blockchain::{Block, BlockError, Blockchain, ChainParams, Payload, ValidationReport};
+pub use logreg::{RegressionError, RegressionResult, SolverOptions};
+pub use model::{Feature, LogisticModel, Outcome};
+pub use patient::{Patient, PatientError};
@@ -0,0 +1,719 @@
+// Reference code found here: https://paulkernfeld.com/2018/07/01/logistic-regression-in-rust.html
+
+use ndarray::{s, Array1, Array2, Axis};
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+// This value is from scipy.optimize
+// https://docs.scipy.org/doc/scipy/reference/optimize.minimize-lbfgsb.html
+const FTOL: f64 = 2.220446049250313e-09;
+
+const ARMIJO_GOLDSTEIN_CONTROL: f64 = 0.5;
+
+// Smallest fraction of a Newton step the line search tries before giving up
+const MIN_STEP_SCALE: f64 = 1e-10;
+
+// Two-sided 97.5% quantile of the standard normal, used for 95% confidence intervals
+pub(crate) const Z_95: f64 = 1.959963984540054;
+
+//Fitted logistic regression together with its inference statistics. A penalized fit is biased towards zero
+//and its Fisher information ignores the penalty, so its standard errors, z-scores, p-values and confidence
+//intervals are NaN and penalized is set
+#[derive(Serialize, Debug, Clone)]
+pub struct RegressionResult {
+    pub names: Vec<String>,
+    pub coefficients: Array1<f64>,
+    pub std_errors: Array1<f64>,
+    pub z_scores: Array1<f64>,
+    pub p_values: Array1<f64>,
+    pub conf_lower: Array1<f64>,
+    pub conf_upper: Array1<f64>,
+    pub odds_ratios: Array1<f64>,
+    pub log_likelihood: f64,
+    pub aic: f64,
+    pub bic: f64,
+    pub n_obs: usize,
+    pub convergence: Option<Convergence>,
+    pub penalized: bool,
+}
+
+//Optimization algorithm used to fit the coefficients
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum Solver {
+    GradientDescent,
+    Newton
+}
+
+//Penalty added to the summed log-loss. Column 0 is treated as the intercept and is never penalized
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Penalty {
+    None,
+    Ridge(f64),
+    Lasso(f64),
+    //Strength and the share of it given to the L1 term, between 0 (ridge) and 1 (lasso)
+    ElasticNet(f64, f64)
+}
+
+impl Penalty {
+    pub(crate) fn l1(&self) -> f64 {
+        return match *self {
+            Penalty::Lasso(strength) => strength,
+            Penalty::ElasticNet(strength, l1_ratio) => strength * l1_ratio,
+            _ => 0.0
+        };
+    }
+
+    pub(crate) fn l2(&self) -> f64 {
+        return match *self {
+            Penalty::Ridge(strength) => strength,
+            Penalty::ElasticNet(strength, l1_ratio) => strength * (1.0 - l1_ratio),
+            _ => 0.0
+        };
+    }
+
+    //Same kind of penalty with a different strength
+    pub fn with_strength(&self, strength: f64) -> Penalty {
+        return match *self {
+            Penalty::None => Penalty::None,
+            Penalty::Ridge(_) => Penalty::Ridge(strength),
+            Penalty::Lasso(_) => Penalty::Lasso(strength),
+            Penalty::ElasticNet(_, l1_ratio) => Penalty::ElasticNet(strength, l1_ratio)
+        };
+    }
+
+    //True when either term has a positive strength
+    pub(crate) fn is_penalized(&self) -> bool {
+        return self.l1() > 0.0 || self.l2() > 0.0;
+    }
+
+    //Value of the penalty at beta
+    pub(crate) fn value(&self, beta: &Array1<f64>) -> f64 {
+        let slopes = beta.slice(s![1..]);
+        return self.l1() * slopes.fold(0.0, |b, a| b + a.abs()) + 0.5 * self.l2() * slopes.fold(0.0, |b, a| b + a * a);
+    }
+}
+
+//Options controlling how logistic_regression searches for the coefficients
+#[derive(Debug, Clone)]
+pub struct SolverOptions {
+    pub solver: Solver,
+    pub penalty: Penalty,
+    pub max_iter: usize,
+    pub tol: f64
+}
+
+impl Default for SolverOptions {
+    fn default() -> Self {
+        return Self { solver: Solver::Newton, penalty: Penalty::None, max_iter: 100, tol: 1e-8 };
+    }
+}
+
+//How the solver finished
+#[derive(Serialize, Debug, Clone)]
+pub struct Convergence {
+    pub solver: Solver,
+    pub iterations: usize,
+    pub gradient_norm: f64
+}
+
+#[derive(Debug)]
+//Enum describing why a regression could not be fitted
+pub enum RegressionError {
+    TooFewRows,
+    SingularHessian,
+    NotConverged(usize),
+    StepSizeTooSmall,
+    LineSearchFailed
+}
+
+impl fmt::Display for RegressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            RegressionError::TooFewRows => write!(f, "too few rows to run regression"),
+            RegressionError::SingularHessian => write!(f, "hessian is singular, features may be collinear"),
+            RegressionError::NotConverged(iter) => write!(f, "solver did not converge after {} iterations", iter),
+            RegressionError::StepSizeTooSmall => write!(f, "even a very small value of epsilon didn't work"),
+            RegressionError::LineSearchFailed => write!(f, "no fraction of the Newton step decreased the loss")
+        };
+    }
+}
+
+impl Error for RegressionError {}
+
+    fn norm_l2(a_s: &Array1<f64>) -> f64 {
+        return a_s.fold(0.0, |b, a| b + a * a);
+    }
+
+//Soft-thresholds every coefficient but the intercept, the proximal operator of the L1 term
+fn soft_threshold(beta: &mut Array1<f64>, threshold: f64) {
+    if threshold <= 0.0 {
+        return;
+    }
+    for b in beta.iter_mut().skip(1) {
+        *b = b.signum() * (b.abs() - threshold).max(0.0);
+    }
+}
+
+fn minimize_inner(beta_init: Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, epsilon: f64, options: &SolverOptions) 
+    -> Result<(Array1<f64>, usize), RegressionError> {
+    let mut beta = beta_init;
+    let penalty = options.penalty;
+
+    let (mut prev_loss, mut prev_gradient) = penalized_loss_gradient(&beta, x, y, &penalty);
+
+    for iter in 1..=options.max_iter {
+        beta.scaled_add(-epsilon, &prev_gradient);
+        soft_threshold(&mut beta, epsilon * penalty.l1());
+        let (loss, gradient) = penalized_loss_gradient(&beta, x, y, &penalty);
+        // The step is too long when the loss rises or overflows, the caller retries with half of it
+        if !loss.is_finite() || loss > prev_loss {
+            return Err(RegressionError::StepSizeTooSmall);
+        }
+
+        // Proximal gradient mapping at the new point, which is just the gradient without an L1 term
+        let mut next_beta = &beta - &(&gradient * epsilon);
+        soft_threshold(&mut next_beta, epsilon * penalty.l1());
+        let expected_decrease = norm_l2(&(&beta - &next_beta)) / epsilon;
+        let actual_decrease = prev_loss - loss;
+        if actual_decrease < expected_decrease * ARMIJO_GOLDSTEIN_CONTROL {
+            return Err(RegressionError::StepSizeTooSmall);
+        }
+        if actual_decrease < FTOL {
+            return Ok((beta, iter));
+        }
+        prev_loss = loss;
+        prev_gradient = gradient;
+    }
+    return Err(RegressionError::NotConverged(options.max_iter));
+}
+
+//Gradient descent, retrying with halved step sizes when a step raises the loss or fails the Armijo-Goldstein
+//condition. Running out of iterations is returned as it is, a shorter step would only converge more slowly
+fn minimize(beta_init: Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, options: &SolverOptions) 
+    -> Result<(Array1<f64>, usize), RegressionError> {
+    for i in 0..20 {
+        let epsilon = 2.0_f64.powi(-i);
+
+        match minimize_inner(beta_init.clone(), x, y, epsilon, options) {
+            Err(RegressionError::StepSizeTooSmall) => continue,
+            result => return result
+        }
+    }
+    return Err(RegressionError::StepSizeTooSmall);
+}
+
+//Minimizes the quadratic model g.d + d^T H d / 2 + l1 * |beta + d|_1 by coordinate descent, returns the step d.
+//After each sweep the model is solved exactly on the coefficients the sweep left nonzero, which ends the search
+//once the sweeps have found them. NotConverged when the step still moves after max_iter sweeps
+pub(crate) fn quadratic_l1_step(beta: &Array1<f64>, gradient: &Array1<f64>, hessian: &Array2<f64>, l1: f64, options: &SolverOptions)
+    -> Result<Array1<f64>, RegressionError> {
+    let n = beta.len();
+    let mut step = Array1::<f64>::zeros(n);
+    for _sweep in 0..options.max_iter {
+        let mut max_change: f64 = 0.0;
+        for j in 0..n {
+            if hessian[[j, j]] <= 0.0 {
+                continue;
+            }
+            let cross = hessian.row(j).dot(&step) - hessian[[j, j]] * step[j];
+            let target = hessian[[j, j]] * beta[j] - gradient[j] - cross;
+            let threshold = if j == 0 { 0.0 } else { l1 };
+            let updated = target.signum() * (target.abs() - threshold).max(0.0) / hessian[[j, j]];
+            let new_step = updated - beta[j];
+            max_change = max_change.max((new_step - step[j]).abs());
+            step[j] = new_step;
+        }
+        if max_change < options.tol {
+            return Ok(step);
+        }
+        if let Some(exact) = l1_step_on_support(beta, gradient, hessian, l1, &step, options.tol) {
+            return Ok(exact);
+        }
+    }
+    return Err(RegressionError::NotConverged(options.max_iter));
+}
+
+//Minimizer of the quadratic model among steps leaving the same coefficients at zero, and the others with the same
+//signs, as step does. None unless it is the minimizer of the whole model, within tol of its optimality conditions
+fn l1_step_on_support(beta: &Array1<f64>, gradient: &Array1<f64>, hessian: &Array2<f64>, l1: f64, step: &Array1<f64>, tol: f64)
+    -> Option<Array1<f64>> {
+    let reached = beta + step;
+    let support: Vec<usize> = (0..beta.len()).filter(|&j| j == 0 || reached[j] != 0.0).collect();
+    let signs = support.iter().map(|&j| if j == 0 { 0.0 } else { reached[j].signum() }).collect::<Array1<f64>>();
+    //Coefficients off the support are stepped to zero
+    let mut exact = -beta;
+    for &j in &support {
+        exact[j] = 0.0;
+    }
+    let rhs = -(&gradient.select(Axis(0), &support) + &(&signs * l1) + hessian.dot(&exact).select(Axis(0), &support));
+    let solved = invert(&hessian.select(Axis(0), &support).select(Axis(1), &support))?.dot(&rhs);
+    for (k, &j) in support.iter().enumerate() {
+        exact[j] = solved[k];
+    }
+    //The intercept is always first in the support
+    if support.iter().zip(signs.iter()).skip(1).any(|(&j, sign)| (beta[j] + exact[j]).signum() != *sign) {
+        return None;
+    }
+    let residual = gradient + &hessian.dot(&exact);
+    if (1..beta.len()).filter(|j| !support.contains(j)).any(|j| residual[j].abs() > l1 + tol) {
+        return None;
+    }
+    return Some(exact);
+}
+
+//Backtracking line search along a Newton step from beta: halves the step until evaluate's loss is no higher than
+//loss, returning the point reached and everything evaluate computed there. A step with every coordinate below
+//tol is taken whole, it is converged and rounding alone can raise the loss at the optimum
+pub(crate) fn line_search<V>(beta: &Array1<f64>, step: &Array1<f64>, loss: f64, tol: f64, evaluate: impl Fn(&Array1<f64>) -> (f64, V))
+    -> Result<(Array1<f64>, V), RegressionError> {
+    let converged = step.fold(0.0_f64, |m, d| m.max(d.abs())) < tol;
+    let mut scale = 1.0;
+    while scale >= MIN_STEP_SCALE {
+        let next_beta = beta + &(step * scale);
+        let (next_loss, next) = evaluate(&next_beta);
+        if next_loss <= loss || converged {
+            return Ok((next_beta, next));
+        }
+        scale /= 2.0;
+    }
+    return Err(RegressionError::LineSearchFailed);
+}
+
+//Newton-Raphson, equivalently IRLS, with step halving whenever the loss would increase.
+//With an L1 term each Newton step solves the penalized quadratic model by coordinate descent
+fn minimize_newton(beta_init: Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, options: &SolverOptions) 
+    -> Result<(Array1<f64>, usize), RegressionError> {
+    let penalty = options.penalty;
+    let mut beta = beta_init;
+    let (mut loss, mut gradient) = penalized_loss_gradient(&beta, x, y, &penalty);
+
+    for iter in 1..=options.max_iter {
+        let mut hessian = fisher_information(&beta, x);
+        for j in 1..beta.len() {
+            hessian[[j, j]] += penalty.l2();
+        }
+        let step = if penalty.l1() > 0.0 {
+            quadratic_l1_step(&beta, &gradient, &hessian, penalty.l1(), options)?
+        } else {
+            -invert(&hessian).ok_or(RegressionError::SingularHessian)?.dot(&gradient)
+        };
+
+        let (next_beta, (next_loss, next_gradient)) = line_search(&beta, &step, loss, options.tol, |next_beta| {
+            let (next_loss, next_gradient) = penalized_loss_gradient(next_beta, x, y, &penalty);
+            return (next_loss, (next_loss, next_gradient));
+        })?;
+
+        let max_change = (&next_beta - &beta).fold(0.0_f64, |m, d| m.max(d.abs()));
+        beta = next_beta;
+        loss = next_loss;
+        gradient = next_gradient;
+        if max_change < options.tol {
+            return Ok((beta, iter));
+        }
+    }
+    return Err(RegressionError::NotConverged(options.max_iter));
+}
+
+//Predicted probabilities for each row of x
+pub fn predict(beta_hat: &Array1<f64>, x: &Array2<f64>) -> Array1<f64> {
+    return x.dot(beta_hat).t().map(|a| 1.0 / (1.0 + (-a).exp()));
+}
+
+pub(crate) fn loss_gradient(beta: &Array1<f64>, x: &Array2<f64>, y: &Array1<f64>) -> (f64, Array1<f64>) {
+    let yhats = predict(beta, x);
+    let loss = -y.iter()
+        .zip(yhats.iter())
+        .map(|(y, yhat)| y * yhat.ln() + (1.0 - y) * (1.0 - yhat).ln())
+        .sum::<f64>();
+    let gradient = (yhats - y).dot(x);
+    return (loss, gradient);
+}
+
+//Loss plus penalty. The gradient only covers the smooth part, the L1 term is handled by the solvers
+fn penalized_loss_gradient(beta: &Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, penalty: &Penalty) -> (f64, Array1<f64>) {
+    let (loss, mut gradient) = loss_gradient(beta, x, y);
+    let l2 = penalty.l2();
+    for j in 1..beta.len() {
+        gradient[j] += l2 * beta[j];
+    }
+    return (loss + penalty.value(beta), gradient);
+}
+
+//Fisher information of the log-likelihood at beta, X^T W X with W = p(1 - p)
+pub(crate) fn fisher_information(beta: &Array1<f64>, x: &Array2<f64>) -> Array2<f64> {
+    let yhats = predict(beta, x);
+    let weights = yhats.map(|p| p * (1.0 - p));
+    let mut weighted_x = x.clone();
+    for (mut row, w) in weighted_x.rows_mut().into_iter().zip(weights.iter()) {
+        row *= *w;
+    }
+    return x.t().dot(&weighted_x);
+}
+
+//Inverts a square matrix with Gauss-Jordan elimination, None if it is singular
+pub(crate) fn invert(matrix: &Array2<f64>) -> Option<Array2<f64>> {
+    let n = matrix.nrows();
+    let mut a = matrix.clone();
+    let mut inv = Array2::<f64>::eye(n);
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[[i, col]].abs().total_cmp(&a[[j, col]].abs()))?;
+        if a[[pivot, col]].abs() < 1e-12 {
+            return None;
+        }
+        for k in 0..n {
+            a.swap([col, k], [pivot, k]);
+            inv.swap([col, k], [pivot, k]);
+        }
+        let diag = a[[col, col]];
+        for k in 0..n {
+            a[[col, k]] /= diag;
+            inv[[col, k]] /= diag;
+        }
+        for row in 0..n {
+            if row == col {
+                continue;
+            }
+            let factor = a[[row, col]];
+            for k in 0..n {
+                a[[row, k]] -= factor * a[[col, k]];
+                inv[[row, k]] -= factor * inv[[col, k]];
+            }
+        }
+    }
+    return Some(inv);
+}
+
+//Complementary error function, Numerical Recipes erfc approximation (relative error < 1.2e-7)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let r = t * (-z * z - 1.26551223 + t * (1.00002368 + t * (0.37409196 + t * (0.09678418
+        + t * (-0.18628806 + t * (0.27886807 + t * (-1.13520398 + t * (1.48851587
+        + t * (-0.82215223 + t * 0.17087277))))))))).exp();
+    return if x >= 0.0 { r } else { 2.0 - r };
+}
+
+//Two-sided p-value of a z-score under the standard normal
+pub(crate) fn two_sided_p_value(z: f64) -> f64 {
+    return erfc(z.abs() / std::f64::consts::SQRT_2);
+}
+
+//Builds the inference statistics for fitted coefficients beta
+pub fn regression_result(beta: Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, names: &[String]) -> RegressionResult {
+    let (loss, _) = loss_gradient(&beta, x, y);
+    let information = fisher_information(&beta, x);
+    return result_from_information(beta, -loss, &information, x.nrows(), names);
+}
+
+//Builds the inference statistics from the log-likelihood and Fisher information alone, without the rows
+pub(crate) fn result_from_information(beta: Array1<f64>, log_likelihood: f64, information: &Array2<f64>, n_obs: usize, names: &[String]) -> RegressionResult {
+    let n_features = beta.len();
+    let std_errors = match invert(information) {
+        Some(covariance) => covariance.diag().map(|v| v.max(0.0).sqrt()),
+        None => Array1::from_elem(n_features, f64::NAN),
+    };
+    let z_scores = &beta / &std_errors;
+    let p_values = z_scores.map(|z| two_sided_p_value(*z));
+    let conf_lower = &beta - &(&std_errors * Z_95);
+    let conf_upper = &beta + &(&std_errors * Z_95);
+    let odds_ratios = beta.map(|b| b.exp());
+    let k = n_features as f64;
+    let names = (0..n_features)
+        .map(|i| names.get(i).cloned().unwrap_or_else(|| format!("x{}", i)))
+        .collect();
+    return RegressionResult {
+        names,
+        coefficients: beta,
+        std_errors,
+        z_scores,
+        p_values,
+        conf_lower,
+        conf_upper,
+        odds_ratios,
+        log_likelihood,
+        aic: 2.0 * k - 2.0 * log_likelihood,
+        bic: k * (n_obs as f64).ln() - 2.0 * log_likelihood,
+        n_obs,
+        convergence: None,
+        penalized: false,
+    };
+}
+
+pub fn logistic_regression(x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions) 
+    -> Result<RegressionResult, RegressionError> {
+    let (n_data_points, n_features) = x.dim();
+    if n_data_points < n_features {
+        return Err(RegressionError::TooFewRows);
+    }
+    return fit(Array1::zeros(n_features), x, y, names, options);
+}
+
+fn fit(beta_hat: Array1<f64>, x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions) 
+    -> Result<RegressionResult, RegressionError> {
+    let (beta, iterations) = match options.solver {
+        Solver::GradientDescent => minimize(beta_hat, x, y, options)?,
+        Solver::Newton => minimize_newton(beta_hat, x, y, options)?
+    };
+    let (_, gradient) = penalized_loss_gradient(&beta, x, y, &options.penalty);
+    let mut result = regression_result(beta, x, y, names);
+    result.convergence = Some(Convergence { solver: options.solver, iterations, gradient_norm: norm_l2(&gradient).sqrt() });
+    if options.penalty.is_penalized() {
+        result.suppress_inference();
+    }
+    return Ok(result);
+}
+
+//Smallest L1 strength at which every slope of a lasso fit is zero
+pub fn max_penalty(x: &Array2<f64>, y: &Array1<f64>) -> f64 {
+    let mean = y.mean().unwrap_or(0.0);
+    let residuals = y.map(|v| v - mean);
+    let correlations = residuals.dot(x);
+    return correlations.iter().skip(1).fold(0.0_f64, |m, c| m.max(c.abs()));
+}
+
+// Ridge strength as a multiple of the largest slope curvature, at which every slope is shrunk to about 1%
+const RIDGE_SHRINKAGE: f64 = 100.0;
+
+//No ridge strength zeroes a slope. Near the intercept-only fit each slope is scaled by about h / (h + strength),
+//h its diagonal of the Fisher information, so this is the strength shrinking every slope to about 1% of its size
+pub fn max_ridge_penalty(x: &Array2<f64>, y: &Array1<f64>) -> f64 {
+    let mean = y.mean().unwrap_or(0.0);
+    let curvature = (1..x.ncols())
+        .map(|j| {
+            let column = x.column(j);
+            let column_mean = column.mean().unwrap_or(0.0);
+            return mean * (1.0 - mean) * column.fold(0.0, |b, v| b + (v - column_mean) * (v - column_mean));
+        })
+        .fold(0.0_f64, f64::max);
+    return RIDGE_SHRINKAGE * curvature;
+}
+
+//Log-spaced grid of num strengths of the given kind of penalty, from the strongest worth fitting down to it
+//times min_ratio. That is max_penalty for lasso, max_penalty over the L1 share for elastic net, which zeroes
+//every slope as well, and max_ridge_penalty for ridge
+pub fn penalty_grid(x: &Array2<f64>, y: &Array1<f64>, penalty: &Penalty, num: usize, min_ratio: f64) -> Vec<f64> {
+    let max = match *penalty {
+        Penalty::None => return vec![0.0],
+        Penalty::Lasso(_) => max_penalty(x, y),
+        Penalty::ElasticNet(_, l1_ratio) if l1_ratio > 0.0 => max_penalty(x, y) / l1_ratio,
+        Penalty::Ridge(_) | Penalty::ElasticNet(_, _) => max_ridge_penalty(x, y)
+    };
+    if num < 2 {
+        return vec![max];
+    }
+    return (0..num)
+        .map(|i| max * min_ratio.powf(i as f64 / (num - 1) as f64))
+        .collect();
+}
+
+//Fits options.penalty at each strength, strongest first, warm starting every fit from the previous one
+pub fn regularization_path(x: &Array2<f64>, y: &Array1<f64>, names: &[String], options: &SolverOptions, strengths: &[f64]) 
+    -> Result<Vec<(f64, RegressionResult)>, RegressionError> {
+    let (n_data_points, n_features) = x.dim();
+    if n_data_points < n_features {
+        return Err(RegressionError::TooFewRows);
+    }
+    let mut sorted = strengths.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+
+    let mut path = Vec::new();
+    let mut beta = Array1::zeros(n_features);
+    for strength in sorted {
+        let step_options = SolverOptions { penalty: options.penalty.with_strength(strength), ..options.clone() };
+        let result = fit(beta, x, y, names, &step_options)?;
+        beta = result.coefficients.clone();
+        path.push((strength, result));
+    }
+    return Ok(path);
+}
+
+impl RegressionResult {
+    //Clears the statistics that are only valid for an unpenalized maximum likelihood fit
+    pub(crate) fn suppress_inference(&mut self) {
+        for column in [&mut self.std_errors, &mut self.z_scores, &mut self.p_values, &mut self.conf_lower, &mut self.conf_upper] {
+            column.fill(f64::NAN);
+        }
+        self.penalized = true;
+    }
+
+    //Formats the coefficient table and model fit statistics
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("Logistic regression, {} observations\n", self.n_obs));
+        out.push_str(&format!("Log-likelihood: {:.4}   AIC: {:.4}   BIC: {:.4}\n", self.log_likelihood, self.aic, self.bic));
+        if let Some(conv) = &self.convergence {
+            out.push_str(&format!("Solver: {:?}, converged in {} iterations, gradient norm {:.3e}\n", conv.solver, conv.iterations, conv.gradient_norm));
+        }
+        out.push('\n');
+        if self.penalized {
+            out.push_str("Penalized fit: standard errors, p-values and confidence intervals are not valid and not reported\n\n");
+            out.push_str(&format!("{:<16} {:>10} {:>10}\n", "", "coef", "odds"));
+            for i in 0..self.names.len() {
+                out.push_str(&format!("{:<16} {:>10.4} {:>10.4}\n", self.names[i], self.coefficients[i], self.odds_ratios[i]));
+            }
+            return out;
+        }
+        out.push_str(&format!("{:<16} {:>10} {:>10} {:>9} {:>9} {:>10} {:>10} {:>10}\n",
+            "", "coef", "std err", "z", "P>|z|", "[0.025", "0.975]", "odds"));
+        for i in 0..self.names.len() {
+            out.push_str(&format!("{:<16} {:>10.4} {:>10.4} {:>9.3} {:>9.4} {:>10.4} {:>10.4} {:>10.4}\n",
+                self.names[i], self.coefficients[i], self.std_errors[i], self.z_scores[i],
+                self.p_values[i], self.conf_lower[i], self.conf_upper[i], self.odds_ratios[i]));
+        }
+        return out;
+    }
+}
+
+impl fmt::Display for RegressionResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}", self.summary());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn invert_test() {
+        let m = array![[4.0, 7.0], [2.0, 6.0]];
+        let inv = invert(&m).unwrap();
+        let identity = m.dot(&inv);
+        assert!((identity[[0, 0]] - 1.0).abs() < 1e-9 && identity[[0, 1]].abs() < 1e-9);
+        assert!(invert(&array![[1.0, 2.0], [2.0, 4.0]]).is_none());
+    }
+
+    #[test]
+    fn newton_matches_gradient_descent_test() {
+        let x = array![[1.0, 0.0], [1.0, 1.0], [1.0, 2.0], [1.0, 3.0], [1.0, 4.0], [1.0, 5.0]];
+        let y = array![0.0, 0.0, 1.0, 0.0, 1.0, 1.0];
+        let names = vec!["intercept".to_string(), "x".to_string()];
+        let newton = logistic_regression(&x, &y, &names, &SolverOptions::default()).unwrap();
+        let gd_options = SolverOptions { solver: Solver::GradientDescent, max_iter: 100_000, ..SolverOptions::default() };
+        let gd = logistic_regression(&x, &y, &names, &gd_options).unwrap();
+        for i in 0..2 {
+            assert!((newton.coefficients[i] - gd.coefficients[i]).abs() < 1e-3);
+        }
+        assert!(newton.convergence.unwrap().iterations < 20);
+    }
+
+    #[test]
+    fn gradient_descent_step_test() {
+        //Ages in years make the first full step overflow the loss, so it is halved until the loss falls
+        let x = array![[1.0, 30.0], [1.0, 45.0], [1.0, 50.0], [1.0, 62.0], [1.0, 70.0], [1.0, 81.0]];
+        let y = array![0.0, 1.0, 0.0, 1.0, 0.0, 1.0];
+        let names = vec!["intercept".to_string(), "age".to_string()];
+        let options = SolverOptions { solver: Solver::GradientDescent, max_iter: 1_000_000, ..SolverOptions::default() };
+        let gd = logistic_regression(&x, &y, &names, &options).unwrap();
+        let newton = logistic_regression(&x, &y, &names, &SolverOptions::default()).unwrap();
+        assert!((gd.coefficients[1] - newton.coefficients[1]).abs() < 1e-3);
+        //Running out of iterations is reported as such rather than retried with ever shorter steps
+        let options = SolverOptions { max_iter: 5, ..options };
+        assert!(matches!(logistic_regression(&x, &y, &names, &options), Err(RegressionError::NotConverged(5))));
+    }
+
+    #[test]
+    fn quadratic_l1_step_test() {
+        let (beta, gradient, hessian) = (array![0.0, 0.0, 0.5], array![1.0, 1.0, -0.2], array![[1.0, 0.9, 0.3], [0.9, 1.0, 0.2], [0.3, 0.2, 1.0]]);
+        let step = quadratic_l1_step(&beta, &gradient, &hessian, 0.1, &SolverOptions::default()).unwrap();
+        let residual = &hessian.dot(&step) + &gradient;
+        assert_eq!(beta[1] + step[1], 0.0);
+        assert!(residual[0].abs() < 1e-12 && residual[1].abs() <= 0.1 && (residual[2] + 0.1).abs() < 1e-12);
+        //A model without a minimum is an error rather than a partial step
+        let singular = array![[1.0, 1.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        assert!(matches!(quadratic_l1_step(&beta, &array![1.0, 0.0, -0.2], &singular, 0.1, &SolverOptions::default()),
+            Err(RegressionError::NotConverged(100))));
+    }
+
+    #[test]
+    fn lasso_path_test() {
+        let x = array![[1.0, 0.0, 1.0], [1.0, 1.0, 0.0], [1.0, 2.0, 1.0], [1.0, 3.0, 0.0], [1.0, 4.0, 1.0], [1.0, 5.0, 0.0]];
+        let y = array![0.0, 0.0, 1.0, 0.0, 1.0, 1.0];
+        let names = vec!["intercept".to_string(), "a".to_string(), "b".to_string()];
+        let options = SolverOptions { penalty: Penalty::Lasso(1.0), ..SolverOptions::default() };
+        let strengths = penalty_grid(&x, &y, &options.penalty, 5, 0.01);
+        let path = regularization_path(&x, &y, &names, &options, &strengths).unwrap();
+        assert_eq!(path.len(), 5);
+        let strongest = &path[0].1.coefficients;
+        assert!(strongest[1].abs() < 1e-6 && strongest[2].abs() < 1e-6);
+        assert!(path[4].1.coefficients[1] > 0.0);
+
+        let gd_options = SolverOptions { solver: Solver::GradientDescent, penalty: Penalty::Lasso(0.5), max_iter: 100_000, tol: 1e-8 };
+        let newton_options = SolverOptions { penalty: Penalty::Lasso(0.5), ..SolverOptions::default() };
+        let gd = logistic_regression(&x, &y, &names, &gd_options).unwrap();
+        let newton = logistic_regression(&x, &y, &names, &newton_options).unwrap();
+        for i in 0..3 {
+            assert!((gd.coefficients[i] - newton.coefficients[i]).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn penalty_grid_test() {
+        let x = array![[1.0, 0.0, 1.0], [1.0, 1.0, 0.0], [1.0, 2.0, 1.0], [1.0, 3.0, 0.0], [1.0, 4.0, 1.0], [1.0, 5.0, 0.0]];
+        let y = array![0.0, 0.0, 1.0, 0.0, 1.0, 1.0];
+        let names = vec!["intercept".to_string(), "a".to_string(), "b".to_string()];
+        let fit_at = |penalty: Penalty| {
+            let strongest = penalty_grid(&x, &y, &penalty, 3, 0.1)[0];
+            let options = SolverOptions { penalty: penalty.with_strength(strongest), ..SolverOptions::default() };
+            return (strongest, logistic_regression(&x, &y, &names, &options).unwrap().coefficients);
+        };
+        //Half the penalty is L1, so zeroing the slopes takes twice the lasso strength
+        let (strongest, coefficients) = fit_at(Penalty::ElasticNet(1.0, 0.5));
+        assert!((strongest - 2.0 * max_penalty(&x, &y)).abs() < 1e-12);
+        assert!(coefficients[1].abs() < 1e-6 && coefficients[2].abs() < 1e-6);
+        let weaker = SolverOptions { penalty: Penalty::ElasticNet(0.9 * strongest, 0.5), ..SolverOptions::default() };
+        assert!(logistic_regression(&x, &y, &names, &weaker).unwrap().coefficients[1].abs() > 1e-6);
+
+        let unpenalized = logistic_regression(&x, &y, &names, &SolverOptions::default()).unwrap().coefficients;
+        let (strongest, coefficients) = fit_at(Penalty::Ridge(1.0));
+        assert!(strongest > max_penalty(&x, &y));
+        for j in 1..3 {
+            assert!(coefficients[j].abs() < 0.02 * unpenalized[j].abs());
+        }
+        assert_eq!(penalty_grid(&x, &y, &Penalty::None, 3, 0.1), [0.0]);
+    }
+
+    #[test]
+    fn line_search_test() {
+        let beta = array![1.0, 1.0];
+        let quadratic = |b: &Array1<f64>| (b.dot(b), ());
+        let (halved, _) = line_search(&beta, &array![-4.0, -4.0], 2.0, 1e-8, quadratic).unwrap();
+        assert_eq!(halved, array![-1.0, -1.0]);
+        //The loss rises along the step however short it is, which must not pass for convergence
+        assert!(matches!(line_search(&beta, &array![1.0, 0.0], 2.0, 1e-8, quadratic), Err(RegressionError::LineSearchFailed)));
+        assert!(matches!(line_search(&beta, &array![-1.0, 0.0], 2.0, 1e-8, |_: &Array1<f64>| (f64::NAN, ())),
+            Err(RegressionError::LineSearchFailed)));
+        assert!(line_search(&beta, &array![1e-9, 0.0], 2.0, 1e-8, quadratic).is_ok());
+    }
+
+    #[test]
+    fn inference_test() {
+        //x = 0: 10 of 30 positive, x = 1: 20 of 30. A saturated model of a 2x2 table has the closed form
+        //log odds and Woolf standard errors sqrt(1/a + 1/b + 1/c + 1/d)
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| if j == 0 || i >= 30 { 1.0 } else { 0.0 });
+        let y = Array1::from_shape_fn(60, |i| if i < 10 || (30..50).contains(&i) { 1.0 } else { 0.0 });
+        let names = vec!["intercept".to_string(), "x".to_string()];
+        let fit = logistic_regression(&x, &y, &names, &SolverOptions::default()).unwrap();
+        let reference = [(-std::f64::consts::LN_2, 0.3872983346207417, 0.0735024219093944), (2.0 * std::f64::consts::LN_2, 0.5477225575051661, 0.011373277615037822)];
+        for (i, (coefficient, std_error, p_value)) in reference.iter().enumerate() {
+            assert!((fit.coefficients[i] - coefficient).abs() < 1e-7);
+            assert!((fit.std_errors[i] - std_error).abs() < 1e-7);
+            assert!((fit.p_values[i] - p_value).abs() < 1e-6);
+        }
+        assert!(!fit.penalized);
+
+        let ridge = logistic_regression(&x, &y, &names, &SolverOptions { penalty: Penalty::Ridge(1.0), ..SolverOptions::default() }).unwrap();
+        assert!(ridge.penalized && ridge.coefficients[1] < fit.coefficients[1]);
+        assert!(ridge.std_errors.iter().chain(ridge.p_values.iter()).all(|v| v.is_nan()));
+        assert!(ridge.summary().contains("not reported"));
+    }
+
+    #[test]
+    fn p_value_test() {
+        assert!((two_sided_p_value(0.0) - 1.0).abs() < 1e-6);
+        assert!((two_sided_p_value(Z_95) - 0.05).abs() < 1e-6);
+        assert!((two_sided_p_value(-Z_95) - 0.05).abs() < 1e-6);
+    }
+}
\ No newline at end of file
@@ -1,22 +1,432 @@
-mod bin;
+// The codebase returns explicitly from every function
+#![allow(clippy::needless_return)]
 
-use crate::bin::lib::blockchain::Blockchain;
-use crate::bin::lib::logreg;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-fn main() {
-    let start_patient_idx: usize = 0;
-    let length: usize = 50;
+use carle_chain::api::ApiServer;
+use carle_chain::blockchain::DuplicatePolicy;
+use carle_chain::export::{ExportError, ExportFormat, ExportOptions, Filter};
+use carle_chain::logreg::{Penalty, Solver};
+use carle_chain::mempool::{BlockProducer, Mempool};
+use carle_chain::{Blockchain, ChainParams, Feature, Outcome, Patient, Payload, SolverOptions};
+use clap::{Parser, Subcommand, ValueEnum};
 
-    let mut blockchain: Blockchain = Blockchain::new();
-    let file_path = "data/covid.csv".to_string();
-    blockchain.csv_to_blockchain_range(&file_path, start_patient_idx, length).unwrap(); 
+const OUTCOMES: [Outcome; 5] = [Outcome::Died, Outcome::Icu, Outcome::Intubated, Outcome::CovidPositive, Outcome::Hospitalized];
 
-    if blockchain.validate_chain() {
-        println!("\n\n✔️  VALIDATED BLOCKCHAIN\n");
-    } else {
-        println!("\n\n❌ BROKEN BLOCKCHAIN\n");
+#[derive(Parser)]
+#[command(name = "carle_chain", version, about = "Build, check and analyse a CarleChain ledger of patient records")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Mine the patients of a dataset CSV into a new chain file
+    Ingest {
+        /// Dataset CSV in the Kaggle COVID-19 layout
+        #[arg(long, default_value = "data/covid.csv")]
+        csv: String,
+        /// First patient row to read, reads only --length rows on one thread when given
+        #[arg(long, requires = "length")]
+        start: Option<usize>,
+        /// Number of patient rows to read from --start
+        #[arg(long, requires = "start")]
+        length: Option<usize>,
+        /// Reader threads when ingesting the whole file
+        #[arg(long, default_value_t = 8)]
+        threads: usize,
+        /// Leading zero hex digits required of every block hash
+        #[arg(long, default_value_t = ChainParams::default().difficulty)]
+        difficulty: usize,
+        /// Network the chain belongs to
+        #[arg(long, default_value_t = ChainParams::default().chain_id)]
+        chain_id: String,
+        /// What to do with a patient id that is already on the chain
+        #[arg(long, value_enum, default_value_t = DuplicatesArg::Reject)]
+        duplicates: DuplicatesArg,
+        /// Hospital recorded on every block mined, e.g. the site running this node
+        #[arg(long)]
+        hospital: Option<String>,
+        /// Chain file to write
+        #[arg(long, default_value = "chain.json")]
+        out: String
+    },
+    /// Check every block of a chain file and list the invalid ones
+    Validate {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        #[arg(long, default_value_t = 8)]
+        threads: usize
+    },
+    /// Show the chain parameters and head, or a single block by id or hash
+    Inspect {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        #[arg(long, conflicts_with = "hash")]
+        block: Option<u64>,
+        #[arg(long)]
+        hash: Option<String>
+    },
+    /// Print every block holding a patient's records
+    Query {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        #[arg(long)]
+        patient: String
+    },
+    /// Summary counts of the patients on the chain
+    Stats {
+        #[arg(long, default_value = "chain.json")]
+        chain: String
+    },
+    /// Fit a logistic regression of an outcome on the default features
+    Regress {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        /// Dataset column of the outcome: if_died, icu, intubed, covid_res or patient_type
+        #[arg(long, default_value = "if_died", value_parser = parse_outcome)]
+        outcome: Outcome,
+        #[arg(long, value_enum, default_value_t = SolverArg::Newton)]
+        solver: SolverArg,
+        /// L2 penalty strength
+        #[arg(long, conflicts_with = "lasso")]
+        ridge: Option<f64>,
+        /// L1 penalty strength
+        #[arg(long)]
+        lasso: Option<f64>,
+        #[arg(long, default_value_t = SolverOptions::default().max_iter)]
+        max_iter: usize
+    },
+    /// Write the blocks of a chain file as JSON, or flattened to one row per block as CSV, JSON Lines or Parquet
+    Export {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        #[arg(long, value_enum, default_value_t = FormatArg::Json)]
+        format: FormatArg,
+        /// Output file, standard output when omitted. Required for parquet
+        #[arg(long, required_if_eq("format", "parquet"))]
+        out: Option<String>,
+        /// Comma separated columns to write, in order, e.g. block_id,timestamp,id,age
+        #[arg(long, value_delimiter = ',')]
+        fields: Option<Vec<String>>,
+        /// Condition rows must meet, e.g. age>=60 or icu=Yes. Can be repeated
+        #[arg(long = "filter")]
+        filters: Vec<String>
+    },
+    /// Re-read a chain file under the current patient schema and save it. Blocks mined under an older schema
+    /// keep their original encoding next to the migrated record, so their hashes still verify, and model
+    /// anchors saved before anchors were hashed are mined
+    Migrate {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        /// Chain file to write, --chain is rewritten when omitted
+        #[arg(long)]
+        out: Option<String>
+    },
+    /// Serve the chain file over a local HTTP/JSON API. Submitted patients are queued and mined in the
+    /// background, and the chain file is saved after every mined batch
+    Serve {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Patients the producer takes from the queue at a time
+        #[arg(long, default_value_t = 16)]
+        batch_size: usize,
+        /// Most patients waiting to be mined before submissions are refused
+        #[arg(long, default_value_t = 10_000)]
+        mempool_capacity: usize,
+        /// Hospital recorded on every block mined, e.g. the site running this node
+        #[arg(long)]
+        hospital: Option<String>
+    },
+    /// Append dataset CSV rows read from standard input to a chain file, creating it if needed
+    Node {
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        /// Save the chain after this many new blocks, as well as on end of input
+        #[arg(long, default_value_t = 100)]
+        save_every: usize,
+        /// Hospital recorded on every block mined, e.g. the site running this node
+        #[arg(long)]
+        hospital: Option<String>
+    },
+    /// Map the patients of a FHIR JSON bundle onto records and append them to a chain file, creating it if
+    /// needed. Prints which resources could not be mapped
+    ImportFhir {
+        bundle: String,
+        #[arg(long, default_value = "chain.json")]
+        chain: String,
+        /// Hospital recorded on every block mined, e.g. the site running this node
+        #[arg(long)]
+        hospital: Option<String>
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DuplicatesArg {
+    Reject,
+    Amend,
+    Allow
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum FormatArg {
+    /// Blocks as saved in the chain file
+    Json,
+    Csv,
+    Jsonl,
+    Parquet
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SolverArg {
+    Newton,
+    GradientDescent
+}
+
+fn parse_outcome(name: &str) -> Result<Outcome, String> {
+    return Outcome::from_name(name).ok_or(format!("unknown outcome {}", name));
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::init();
+    return run(Cli::parse());
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Ingest { csv, start, length, threads, difficulty, chain_id, duplicates, hospital, out } => {
+            let duplicate_policy = match duplicates {
+                DuplicatesArg::Reject => DuplicatePolicy::Reject,
+                DuplicatesArg::Amend => DuplicatePolicy::Amend,
+                DuplicatesArg::Allow => DuplicatePolicy::AllowWithWarning
+            };
+            let params = ChainParams { chain_id, difficulty, duplicate_policy, ..ChainParams::default() };
+            let mut blockchain = Blockchain::with_params(params);
+            blockchain.hospital = hospital;
+            match (start, length) {
+                (Some(start), Some(length)) => blockchain.csv_to_blockchain_range(&csv, start, length)?,
+                _ => blockchain.csv_to_blockchain_threaded(&csv, threads)?
+            }
+            blockchain.save(&out)?;
+            println!("wrote {} blocks to {}", blockchain.blocks.len(), out);
+        },
+        Command::Validate { chain, threads } => {
+            let report = Blockchain::<Patient>::load(&chain)?.validate_chain_report(threads);
+            if !report.is_valid() {
+                println!("\n\n❌ BROKEN BLOCKCHAIN\n");
+                println!("{}", report);
+                return Err(format!("{} is broken, {} blocks and {} model anchors are invalid", chain, report.errors.len(), report.anchor_errors.len()).into());
+            }
+            println!("\n\n✔️  VALIDATED BLOCKCHAIN\n");
+            println!("{}", report);
+        },
+        Command::Inspect { chain, block, hash } => {
+            let blockchain: Blockchain = Blockchain::load(&chain)?;
+            let found = match (block, hash) {
+                (Some(id), _) => Some(blockchain.block(id).ok_or(format!("no block with id {}", id))?),
+                (_, Some(hash)) => Some(blockchain.block_by_hash(&hash).ok_or(format!("no block with hash {}", hash))?),
+                _ => None
+            };
+            match found {
+                Some(block) => println!("{}", serde_json::to_string_pretty(block)?),
+                None => {
+                    println!("{}", serde_json::to_string_pretty(&blockchain.params)?);
+                    println!("blocks: {}", blockchain.blocks.len());
+                    if let Some(head) = blockchain.blocks.last() {
+                        println!("head: {} {}", head.id, head.hash);
+                    }
+                    println!("model anchors: {}", blockchain.model_anchors.len());
+                }
+            }
+        },
+        Command::Query { chain, patient } => {
+            let blockchain: Blockchain = Blockchain::load(&chain)?;
+            let blocks = blockchain.blocks_for(&patient);
+            if blocks.is_empty() {
+                return Err(format!("no records for patient {}", patient).into());
+            }
+            println!("{}", serde_json::to_string_pretty(&blocks)?);
+        },
+        Command::Stats { chain } => print_stats(&Blockchain::load(&chain)?),
+        Command::Regress { chain, outcome, solver, ridge, lasso, max_iter } => {
+            let penalty = match (ridge, lasso) {
+                (Some(strength), _) => Penalty::Ridge(strength),
+                (_, Some(strength)) => Penalty::Lasso(strength),
+                _ => Penalty::None
+            };
+            let solver = match solver {
+                SolverArg::Newton => Solver::Newton,
+                SolverArg::GradientDescent => Solver::GradientDescent
+            };
+            let options = SolverOptions { solver, penalty, max_iter, ..SolverOptions::default() };
+            println!("{}", Blockchain::load(&chain)?.run_regression(outcome, &options)?);
+        },
+        Command::Export { chain, format, out, fields, filters } => {
+            let blockchain: Blockchain = Blockchain::load(&chain)?;
+            let format = match format {
+                FormatArg::Json if fields.is_some() || !filters.is_empty() => return Err("--fields and --filter need a flat format: csv, jsonl or parquet".into()),
+                FormatArg::Json => {
+                    let json = serde_json::to_string_pretty(&blockchain.blocks)?;
+                    match out {
+                        Some(path) => std::fs::write(path, json)?,
+                        None => println!("{}", json)
+                    }
+                    return Ok(());
+                },
+                FormatArg::Csv => ExportFormat::Csv,
+                FormatArg::Jsonl => ExportFormat::JsonLines,
+                FormatArg::Parquet => ExportFormat::Parquet
+            };
+            let filters = filters.iter().map(|filter| Filter::parse(filter)).collect::<Result<Vec<Filter>, ExportError>>()?;
+            let options = ExportOptions { format, fields, filters };
+            let rows = match &out {
+                Some(path) => blockchain.export(&options, BufWriter::new(File::create(path)?))?,
+                None => blockchain.export(&options, io::stdout())?
+            };
+            if let Some(path) = out {
+                println!("wrote {} rows to {}", rows, path);
+            }
+        },
+        Command::Migrate { chain, out } => {
+            let mut blockchain: Blockchain = Blockchain::load(&chain)?;
+            let sealed = blockchain.seal_anchors();
+            if sealed > 0 {
+                println!("hashed {} model anchors saved before anchors were hashed", sealed);
+            }
+            for (version, count) in blockchain.schema_versions() {
+                let current = if version == Patient::SCHEMA_VERSION { " (current)" } else { "" };
+                println!("schema version {}{}: {} blocks", version, current, count);
+            }
+            let report = blockchain.validate_chain_report(8);
+            if !report.is_valid() {
+                return Err(format!("not migrating an invalid chain\n{}", report).into());
+            }
+            let out = out.unwrap_or(chain);
+            blockchain.save(&out)?;
+            println!("wrote {} blocks to {}", blockchain.blocks.len(), out);
+        },
+        Command::Serve { chain, addr, batch_size, mempool_capacity, hospital } => {
+            let mut blockchain = if Path::new(&chain).exists() { Blockchain::load(&chain)? } else { Blockchain::new() };
+            blockchain.hospital = hospital;
+            let blockchain = Arc::new(Mutex::new(blockchain));
+            let mempool = Arc::new(Mempool::new(mempool_capacity));
+            let producer = BlockProducer::spawn(Arc::clone(&blockchain), Arc::clone(&mempool), batch_size, Some(chain));
+            let mut server = ApiServer::bind(&addr, blockchain).map_err(|err| err.to_string())?;
+            server.mempool = Some(mempool);
+            println!("serving on http://{}", addr);
+            server.run();
+            producer.shutdown();
+        },
+        Command::Node { chain, save_every, hospital } => {
+            let mut blockchain = if Path::new(&chain).exists() { Blockchain::load(&chain)? } else { Blockchain::new() };
+            blockchain.hospital = hospital;
+            let mut reader = csv::Reader::from_reader(io::stdin());
+            let mut added = 0;
+            let mut skipped = 0;
+            for (row, record) in reader.records().enumerate() {
+                let patient = match record {
+                    Ok(record) => Patient::from_record(&record).map_err(|err| err.to_string()),
+                    Err(err) if err.is_io_error() => {
+                        blockchain.save(&chain)?;
+                        return Err(err.into());
+                    },
+                    Err(err) => Err(err.to_string())
+                };
+                let patient = match patient {
+                    Ok(patient) => patient,
+                    Err(err) => {
+                        eprintln!("skipping record {}: {}", row + 1, err);
+                        skipped += 1;
+                        continue;
+                    }
+                };
+                let before = blockchain.blocks.len();
+                blockchain.add_patient_struct(patient);
+                if let Some(block) = blockchain.blocks.get(before) {
+                    println!("block {} {}", block.id, block.hash);
+                    added += 1;
+                    if added % save_every.max(1) == 0 {
+                        blockchain.save(&chain)?;
+                    }
+                }
+            }
+            blockchain.save(&chain)?;
+            println!("added {} blocks, skipped {} rows, chain height {}", added, skipped, blockchain.blocks.len());
+        },
+        Command::ImportFhir { bundle, chain, hospital } => {
+            let mut blockchain = if Path::new(&chain).exists() { Blockchain::load(&chain)? } else { Blockchain::new() };
+            blockchain.hospital = hospital;
+            let report = blockchain.import_fhir(&bundle)?;
+            print!("{}", report);
+            blockchain.save(&chain)?;
+            println!("chain height {}", blockchain.blocks.len());
+        }
+    }
+    return Ok(());
+}
+
+fn print_stats(blockchain: &Blockchain) {
+    let n = blockchain.blocks.len();
+    println!("blocks: {}", n);
+    if n == 0 {
+        return;
+    }
+    let mean_age = blockchain.patients().map(|patient| patient.feature(Feature::Age)).sum::<f64>() / n as f64;
+    println!("mean age: {:.1}", mean_age);
+    if let Some(date) = blockchain.last_recorded_date() {
+        println!("last recorded date: {}", date);
+    }
+    println!("{:<14} {:>8} {:>8} {:>8}", "outcome", "known", "positive", "rate");
+    for outcome in OUTCOMES {
+        let targets: Vec<f64> = blockchain.patients().filter_map(|patient| patient.outcome(outcome)).collect();
+        let positive = targets.iter().sum::<f64>();
+        let rate = if targets.is_empty() { f64::NAN } else { positive / targets.len() as f64 };
+        println!("{:<14} {:>8} {:>8} {:>8.4}", outcome.name(), targets.len(), positive, rate);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::CommandFactory;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        return Cli::try_parse_from(["carle_chain"].iter().chain(args));
+    }
+
+    #[test]
+    fn ingest_arguments_test() {
+        Cli::command().debug_assert();
+        assert!(parse(&["ingest", "--start", "5"]).is_err());
+        assert!(parse(&["export", "--format", "parquet"]).is_err());
+
+        let dir = std::env::temp_dir();
+        let csv = dir.join(format!("carle_chain_cli_{}.csv", std::process::id()));
+        let out = dir.join(format!("carle_chain_cli_{}.json", std::process::id()));
+        let (csv, out) = (csv.to_string_lossy().to_string(), out.to_string_lossy().to_string());
+        let ingest = |start: &str, length: &str| run(parse(&["ingest", "--csv", &csv, "--start", start, "--length", length, "--difficulty", "1",
+            "--hospital", "north", "--out", &out]).unwrap());
+        assert!(ingest("0", "1").is_err());
+        std::fs::write(&csv, format!("{}\np1,2,1,10-05-2020,08-05-2020,9999-99-99,97,1,35,2,2,2,2,2,2,2,2,2,2,2,99,1,97",
+            carle_chain::patient::COLUMN_NAMES.join(","))).unwrap();
+        assert!(ingest("1", "1").is_err());
+        ingest("0", "1").unwrap();
+        let mut blockchain = Blockchain::<Patient>::load(&out).unwrap();
+        assert_eq!((blockchain.blocks.len(), blockchain.blocks[0].hospital.as_deref()), (1, Some("north")));
+
+        //A broken chain fails the command so scripts see a non-zero exit
+        run(parse(&["validate", "--chain", &out]).unwrap()).unwrap();
+        blockchain.blocks[0].nonce += 1;
+        blockchain.save(&out).unwrap();
+        assert!(run(parse(&["validate", "--chain", &out]).unwrap()).is_err());
+        std::fs::remove_file(&csv).unwrap();
+        std::fs::remove_file(&out).unwrap();
     }
-    
-    let reg_coeffs = blockchain.run_regression();
-    println!("{:?}", reg_coeffs);
-}
\ No newline at end of file
+}
@@ -0,0 +1,196 @@
+// Pool of submitted records waiting to be mined, and the background producer that mines them.
+// Submitting only checks and queues the record, so callers return before any proof of work is done.
+
+use std::collections::{HashSet, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+use super::blockchain::{Blockchain, Patient, PatientError, Payload};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+//Enum describing why a record was not queued
+pub enum MempoolError<E = PatientError> {
+    //The record failed its payload checks
+    Invalid(E),
+    //Id of a record already waiting in the pool
+    Duplicate(String),
+    Full,
+    Closed
+}
+
+impl<E: fmt::Display> fmt::Display for MempoolError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            MempoolError::Invalid(err) => write!(f, "{}", err),
+            MempoolError::Duplicate(id) => write!(f, "record {} is already pending", id),
+            MempoolError::Full => write!(f, "mempool is full"),
+            MempoolError::Closed => write!(f, "mempool is closed")
+        };
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> Error for MempoolError<E> {}
+
+#[derive(Debug)]
+struct Pool<T> {
+    pending: VecDeque<T>,
+    ids: HashSet<String>,
+    closed: bool
+}
+
+//First-in first-out queue of pending records, safe to share between submitters and the producer
+#[derive(Debug)]
+pub struct Mempool<T = Patient> {
+    pool: Mutex<Pool<T>>,
+    ready: Condvar,
+    pub capacity: usize
+}
+
+impl<T: Payload> Mempool<T> {
+    pub fn new(capacity: usize) -> Self {
+        let pool = Pool { pending: VecDeque::new(), ids: HashSet::new(), closed: false };
+        return Self { pool: Mutex::new(pool), ready: Condvar::new(), capacity };
+    }
+
+    //Checks and queues a record, returns how many records are now pending
+    pub fn submit(&self, record: T) -> Result<usize, MempoolError<T::Error>> {
+        record.check().map_err(MempoolError::Invalid)?;
+        let mut pool = self.pool.lock().expect("mempool lock poisoned");
+        if pool.closed {
+            return Err(MempoolError::Closed);
+        } else if pool.ids.contains(record.id()) {
+            return Err(MempoolError::Duplicate(record.id().to_string()));
+        } else if pool.pending.len() >= self.capacity {
+            return Err(MempoolError::Full);
+        }
+        pool.ids.insert(record.id().to_string());
+        pool.pending.push_back(record);
+        self.ready.notify_one();
+        return Ok(pool.pending.len());
+    }
+
+    pub fn len(&self) -> usize {
+        return self.pool.lock().expect("mempool lock poisoned").pending.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    //Waits until records are pending and takes up to max of them in submission order.
+    //None once the pool is closed and empty
+    pub fn take_batch(&self, max: usize) -> Option<Vec<T>> {
+        let mut pool = self.pool.lock().expect("mempool lock poisoned");
+        while pool.pending.is_empty() && !pool.closed {
+            pool = self.ready.wait(pool).expect("mempool lock poisoned");
+        }
+        if pool.pending.is_empty() {
+            return None;
+        }
+        let count = max.max(1).min(pool.pending.len());
+        let batch: Vec<T> = pool.pending.drain(..count).collect();
+        for record in &batch {
+            pool.ids.remove(record.id());
+        }
+        return Some(batch);
+    }
+
+    //Refuses further submissions. Records already pending are still handed out
+    pub fn close(&self) {
+        self.pool.lock().expect("mempool lock poisoned").closed = true;
+        self.ready.notify_all();
+    }
+}
+
+//Background thread mining pending records onto a shared chain, one block per record
+pub struct BlockProducer<T = Patient> {
+    mempool: Arc<Mempool<T>>,
+    handle: JoinHandle<usize>
+}
+
+impl<T: Payload> BlockProducer<T> {
+    //Starts draining mempool into blockchain, batch_size records at a time. The chain lock is released
+    //between blocks so readers are not held up for a whole batch, and the chain is saved after each batch when save_path is set
+    pub fn spawn(blockchain: Arc<Mutex<Blockchain<T>>>, mempool: Arc<Mempool<T>>, batch_size: usize, save_path: Option<String>) -> Self {
+        let pool = Arc::clone(&mempool);
+        let handle = thread::spawn(move || {
+            let mut produced = 0;
+            while let Some(batch) = pool.take_batch(batch_size) {
+                for record in batch {
+                    let mut chain = blockchain.lock().expect("chain lock poisoned");
+                    let height = chain.blocks.len();
+                    chain.add_record(record);
+                    produced += chain.blocks.len() - height;
+                }
+                if let Some(path) = &save_path {
+                    if let Err(err) = blockchain.lock().expect("chain lock poisoned").save(path) {
+                        log::error!("failed to save chain to {}: {}", path, err);
+                    }
+                }
+            }
+            return produced;
+        });
+        return Self { mempool, handle };
+    }
+
+    //Closes the pool, mines whatever is still pending and returns how many blocks were produced
+    pub fn shutdown(self) -> usize {
+        self.mempool.close();
+        return self.handle.join().expect("block producer panicked");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn patient(id: &str) -> Patient {
+        let mut patient = Patient::from_columns(&["x", "1", "2", "10-05-2020", "08-05-2020", "9999-99-99", "2", "2", "40",
+            "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "2", "1", "2"]).unwrap();
+        patient.id = id.to_string();
+        return patient;
+    }
+
+    #[test]
+    fn submit_test() {
+        let pool = Mempool::new(2);
+        assert_eq!(pool.submit(patient("a")), Ok(1));
+        assert_eq!(pool.submit(patient("a")), Err(MempoolError::Duplicate("a".to_string())));
+        assert_eq!(pool.submit(patient("")), Err(MempoolError::Invalid(PatientError::MissingId)));
+        assert_eq!(pool.submit(patient("b")), Ok(2));
+        assert_eq!(pool.submit(patient("c")), Err(MempoolError::Full));
+
+        let batch = pool.take_batch(5).unwrap();
+        assert_eq!(batch.iter().map(|p| p.id.as_str()).collect::<Vec<&str>>(), ["a", "b"]);
+        assert_eq!(pool.submit(patient("a")), Ok(1));
+        pool.close();
+        assert_eq!(pool.submit(patient("d")), Err(MempoolError::Closed));
+        assert_eq!(pool.take_batch(5).map(|batch| batch.len()), Some(1));
+        assert!(pool.take_batch(5).is_none());
+    }
+
+    #[test]
+    fn producer_test() {
+        let chain = Arc::new(Mutex::new(Blockchain::new()));
+        let pool = Arc::new(Mempool::new(100));
+        let producer = BlockProducer::spawn(Arc::clone(&chain), Arc::clone(&pool), 3, None);
+        let submitters: Vec<_> = (0..4).map(|source| {
+            let pool = Arc::clone(&pool);
+            return thread::spawn(move || {
+                for i in 0..5 {
+                    pool.submit(patient(&format!("{}-{}", source, i))).unwrap();
+                }
+            });
+        }).collect();
+        for submitter in submitters {
+            submitter.join().unwrap();
+        }
+        assert_eq!(producer.shutdown(), 20);
+        let chain = chain.lock().unwrap();
+        assert_eq!(chain.blocks.len(), 20);
+        assert!(chain.validate_chain());
+        assert!(pool.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+// Fitted logistic regression models that can score new patients and be stored outside the process
+
+use ndarray::{Array1, Array2};
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::error::Error;
+use std::fs;
+
+use super::blockchain::Patient;
+use super::logreg::{predict, RegressionResult};
+
+//A single column of the design matrix, computed from a Patient
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Intercept,
+    Male,
+    Age,
+    Pneumonia,
+    Pregnancy,
+    Diabetes,
+    Copd,
+    Asthma,
+    Immunosuppressed,
+    Hypertension,
+    OtherDisease,
+    Cardiovascular,
+    Obesity,
+    RenalChronic,
+    Tobacco
+}
+
+//Binary target a model predicts
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Outcome {
+    #[default]
+    Died,
+    Icu,
+    Intubated,
+    CovidPositive,
+    Hospitalized
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Outcome::Died => "if_died",
+            Outcome::Icu => "icu",
+            Outcome::Intubated => "intubed",
+            Outcome::CovidPositive => "covid_res",
+            Outcome::Hospitalized => "patient_type"
+        };
+    }
+
+    //Parses the dataset column name of an outcome
+    pub fn from_name(name: &str) -> Option<Outcome> {
+        return [Outcome::Died, Outcome::Icu, Outcome::Intubated, Outcome::CovidPositive, Outcome::Hospitalized]
+            .into_iter()
+            .find(|outcome| outcome.name() == name);
+    }
+}
+
+//Features used by run_regression
+pub const DEFAULT_FEATURES: [Feature; 6] = [
+    Feature::Intercept, Feature::Male, Feature::Pneumonia, Feature::Diabetes, Feature::Hypertension, Feature::Tobacco
+];
+
+impl Feature {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Feature::Intercept => "intercept",
+            Feature::Male => "male",
+            Feature::Age => "age",
+            Feature::Pneumonia => "pneumonia",
+            Feature::Pregnancy => "pregnancy",
+            Feature::Diabetes => "diabetes",
+            Feature::Copd => "copd",
+            Feature::Asthma => "asthma",
+            Feature::Immunosuppressed => "inmsupr",
+            Feature::Hypertension => "hypertension",
+            Feature::OtherDisease => "other_disease",
+            Feature::Cardiovascular => "cardiovascular",
+            Feature::Obesity => "obesity",
+            Feature::RenalChronic => "renal_chronic",
+            Feature::Tobacco => "tobacco"
+        };
+    }
+}
+
+//Names of each feature, in order
+pub fn feature_names(features: &[Feature]) -> Vec<String> {
+    return features.iter().map(|f| f.name().to_string()).collect();
+}
+
+//Design matrix and 0/1 targets of the patients for whom outcome is known. Patients whose outcome is
+//not applicable or unknown, e.g. ICU for outpatients or a pending COVID test, are left out rather than counted as negatives
+pub fn training_data<'a>(patients: impl Iterator<Item = &'a Patient>, features: &[Feature], outcome: Outcome) -> (Array2<f64>, Array1<f64>) {
+    let (known, targets): (Vec<&Patient>, Vec<f64>) = patients
+        .filter_map(|patient| patient.outcome(outcome).map(|target| (patient, target)))
+        .unzip();
+    return (design_matrix(known.into_iter(), features), Array1::from(targets));
+}
+
+//Builds a design matrix with one row per patient and one column per feature
+pub fn design_matrix<'a>(patients: impl ExactSizeIterator<Item = &'a Patient>, features: &[Feature]) -> Array2<f64> {
+    let mut x_arr = Array2::<f64>::zeros((patients.len(), features.len()));
+    for (idx, patient) in patients.enumerate() {
+        for (col, feature) in features.iter().enumerate() {
+            x_arr[[idx, col]] = patient.feature(*feature);
+        }
+    }
+    return x_arr;
+}
+
+//Coefficients together with the features they apply to and the chain they were trained on
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LogisticModel {
+    #[serde(default)]
+    pub outcome: Outcome,
+    pub features: Vec<Feature>,
+    pub coefficients: Vec<f64>,
+    pub trained_rows: usize,
+    pub chain_height: u64,
+    pub chain_head_hash: String
+}
+
+//Commitment recorded on the chain to a model and the block it was trained up to. Anchors are mined into
+//a chain of their own, each linked to the one before and the first to the chain parameters
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelAnchor {
+    pub model_hash: String,
+    pub block_id: u64,
+    pub block_hash: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub previous_hash: String,
+    #[serde(default)]
+    pub nonce: u64,
+    //Empty for anchors saved before anchors were hashed, until seal_anchors mines them
+    #[serde(default)]
+    pub hash: String
+}
+
+impl LogisticModel {
+    pub fn new(outcome: Outcome, features: &[Feature], result: &RegressionResult, chain_height: u64, chain_head_hash: String) -> Self {
+        return Self {
+            outcome,
+            features: features.to_vec(),
+            coefficients: result.coefficients.to_vec(),
+            trained_rows: result.n_obs,
+            chain_height,
+            chain_head_hash
+        };
+    }
+
+    //Predicted probability of the outcome for one patient
+    pub fn predict(&self, patient: &Patient) -> f64 {
+        return self.predict_all(std::iter::once(patient))[0];
+    }
+
+    //Predicted probabilities for many patients
+    pub fn predict_all<'a>(&self, patients: impl ExactSizeIterator<Item = &'a Patient>) -> Array1<f64> {
+        let x_arr = design_matrix(patients, &self.features);
+        return predict(&Array1::from(self.coefficients.clone()), &x_arr);
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        return serde_json::to_string_pretty(self);
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        return serde_json::from_str(json);
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, self.to_json()?)?;
+        return Ok(());
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        return Ok(Self::from_json(&fs::read_to_string(path)?)?);
+    }
+
+    //SHA256 of the compact JSON encoding, identifies the exact model
+    pub fn hash(&self) -> String {
+        let data = serde_json::to_string(self).expect("model serializes");
+        let mut hasher = Sha256::new();
+        hasher.update(data.as_bytes());
+        return hex::encode(hasher.finalize());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn json_round_trip_test() {
+        let model = LogisticModel {
+            outcome: Outcome::Icu,
+            features: DEFAULT_FEATURES.to_vec(),
+            coefficients: vec![-2.0, 0.5, 1.5, 0.3, 0.2, -0.1],
+            trained_rows: 100,
+            chain_height: 99,
+            chain_head_hash: "000abc".to_string()
+        };
+        let reloaded = LogisticModel::from_json(&model.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded, model);
+        assert_eq!(reloaded.hash(), model.hash());
+
+        let mut changed = model.clone();
+        changed.coefficients[0] = -2.1;
+        assert_ne!(changed.hash(), model.hash());
+    }
+
+    #[test]
+    fn outcome_name_test() {
+        assert_eq!(Outcome::from_name("icu"), Some(Outcome::Icu));
+        assert_eq!(Outcome::from_name("patient_type"), Some(Outcome::Hospitalized));
+        assert_eq!(Outcome::from_name("unknown"), None);
+    }
+}
@@ -0,0 +1,211 @@
+// Online logistic regression kept up to date as blocks are appended to the chain.
+// Patients are grouped by their feature values, and the counts of patients and positive outcomes
+// per group are sufficient statistics: refitting on the groups gives exactly the full-chain model.
+
+use ndarray::{Array1, Array2};
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+
+use super::blockchain::Block;
+use super::federated::{penalize, LocalUpdate};
+use super::logreg::{invert, line_search, quadratic_l1_step, result_from_information, Convergence, RegressionError, RegressionResult, Solver,
+    SolverOptions};
+use super::model::{feature_names, Feature, LogisticModel, Outcome};
+
+//What one block contributed: its hash and, when the outcome is known, the group and whether it was positive
+#[derive(Debug, Clone)]
+struct Observation {
+    hash: String,
+    group: Option<(usize, bool)>
+}
+
+//Sufficient statistics of the chain, updated one block at a time
+#[derive(Debug)]
+pub struct OnlineLearner {
+    pub features: Vec<Feature>,
+    pub outcome: Outcome,
+    patterns: Vec<Vec<f64>>,
+    index: HashMap<Vec<u64>, usize>,
+    //Per group: (patients, positive outcomes)
+    counts: Vec<(f64, f64)>,
+    history: Vec<Observation>
+}
+
+impl OnlineLearner {
+    pub fn new(features: &[Feature], outcome: Outcome) -> Self {
+        return Self { features: features.to_vec(), outcome, patterns: vec![], index: HashMap::new(), counts: vec![], history: vec![] };
+    }
+
+    //Number of blocks observed so far
+    pub fn blocks_seen(&self) -> usize {
+        return self.history.len();
+    }
+
+    //Adds the next block. Blocks must arrive in chain order, anything else is ignored and reported as false
+    pub fn observe(&mut self, block: &Block) -> bool {
+        if block.id != self.history.len() as u64 {
+            return false;
+        }
+        let patient = &block.payload;
+        let group = patient.outcome(self.outcome).map(|target| {
+            let pattern: Vec<f64> = self.features.iter().map(|f| patient.feature(*f)).collect();
+            let key: Vec<u64> = pattern.iter().map(|v| v.to_bits()).collect();
+            let group = match self.index.get(&key) {
+                Some(group) => *group,
+                None => {
+                    self.patterns.push(pattern);
+                    self.counts.push((0.0, 0.0));
+                    self.index.insert(key, self.patterns.len() - 1);
+                    self.patterns.len() - 1
+                }
+            };
+            self.counts[group].0 += 1.0;
+            self.counts[group].1 += target;
+            return (group, target == 1.0);
+        });
+        self.history.push(Observation { hash: block.hash.clone(), group });
+        return true;
+    }
+
+    //Observes every block waiting on a subscription channel without blocking, returns how many were added
+    pub fn sync(&mut self, blocks: &Receiver<Block>) -> usize {
+        let mut added = 0;
+        for block in blocks.try_iter() {
+            if self.observe(&block) {
+                added += 1;
+            }
+        }
+        return added;
+    }
+
+    //Model trained on every block observed so far
+    pub fn snapshot(&self, options: &SolverOptions) -> Result<LogisticModel, RegressionError> {
+        if self.history.is_empty() {
+            return Err(RegressionError::TooFewRows);
+        }
+        return self.fit(self.history.len() - 1, self.counts.clone(), options);
+    }
+
+    //Model trained on blocks 0 through height, as it would have been when that block was appended
+    pub fn snapshot_at(&self, height: u64, options: &SolverOptions) -> Result<LogisticModel, RegressionError> {
+        let height = height as usize;
+        if height >= self.history.len() {
+            return Err(RegressionError::TooFewRows);
+        }
+        let mut counts = vec![(0.0, 0.0); self.patterns.len()];
+        for observation in &self.history[..=height] {
+            if let Some((group, positive)) = observation.group {
+                counts[group].0 += 1.0;
+                counts[group].1 += if positive { 1.0 } else { 0.0 };
+            }
+        }
+        return self.fit(height, counts, options);
+    }
+
+    fn fit(&self, height: usize, counts: Vec<(f64, f64)>, options: &SolverOptions) -> Result<LogisticModel, RegressionError> {
+        let rows: Vec<usize> = (0..counts.len()).filter(|g| counts[*g].0 > 0.0).collect();
+        let mut x = Array2::<f64>::zeros((rows.len(), self.features.len()));
+        for (r, group) in rows.iter().enumerate() {
+            for (c, value) in self.patterns[*group].iter().enumerate() {
+                x[[r, c]] = *value;
+            }
+        }
+        let totals = rows.iter().map(|g| counts[*g].0).collect::<Array1<f64>>();
+        let positives = rows.iter().map(|g| counts[*g].1).collect::<Array1<f64>>();
+        let result = grouped_regression(&x, &totals, &positives, &feature_names(&self.features), options)?;
+        return Ok(LogisticModel::new(self.outcome, &self.features, &result, height as u64, self.history[height].hash.clone()));
+    }
+}
+
+//Loss, gradient and Fisher information of grouped binomial data, equal to those of the ungrouped rows
+fn grouped_terms(beta: &Array1<f64>, x: &Array2<f64>, totals: &Array1<f64>, positives: &Array1<f64>) -> LocalUpdate {
+    let probs = x.dot(beta).map(|a| 1.0 / (1.0 + (-a).exp()));
+    let mut loss = 0.0;
+    for i in 0..probs.len() {
+        if positives[i] > 0.0 {
+            loss -= positives[i] * probs[i].ln();
+        }
+        if totals[i] > positives[i] {
+            loss -= (totals[i] - positives[i]) * (1.0 - probs[i]).ln();
+        }
+    }
+    let gradient = (&probs * totals - positives).dot(x);
+    let weights = probs.map(|p| p * (1.0 - p)) * totals;
+    let mut weighted_x = x.clone();
+    for (mut row, w) in weighted_x.rows_mut().into_iter().zip(weights.iter()) {
+        row *= *w;
+    }
+    return LocalUpdate { n_obs: totals.sum() as usize, loss, gradient, hessian: x.t().dot(&weighted_x) };
+}
+
+//Newton-Raphson on grouped data with the penalty in options added as the federated fit adds it, the solver in
+//options is not used
+fn grouped_regression(x: &Array2<f64>, totals: &Array1<f64>, positives: &Array1<f64>, names: &[String], options: &SolverOptions)
+    -> Result<RegressionResult, RegressionError> {
+    let n_obs = totals.sum();
+    if n_obs < x.ncols() as f64 {
+        return Err(RegressionError::TooFewRows);
+    }
+    let mut beta = Array1::<f64>::zeros(x.ncols());
+    let mut current = penalize(grouped_terms(&beta, x, totals, positives), &beta, options);
+    for iter in 1..=options.max_iter {
+        let step = if options.penalty.l1() > 0.0 {
+            quadratic_l1_step(&beta, &current.gradient, &current.hessian, options.penalty.l1(), options)?
+        } else {
+            -invert(&current.hessian).ok_or(RegressionError::SingularHessian)?.dot(&current.gradient)
+        };
+        let (next_beta, next) = line_search(&beta, &step, current.loss, options.tol, |next_beta| {
+            let next = penalize(grouped_terms(next_beta, x, totals, positives), next_beta, options);
+            return (next.loss, next);
+        })?;
+        let max_change = (&next_beta - &beta).fold(0.0_f64, |m, d| m.max(d.abs()));
+        beta = next_beta;
+        current = next;
+        if max_change < options.tol {
+            let gradient_norm = current.gradient.dot(&current.gradient).sqrt();
+            let unpenalized = grouped_terms(&beta, x, totals, positives);
+            let mut result = result_from_information(beta, -unpenalized.loss, &unpenalized.hessian, n_obs as usize, names);
+            result.convergence = Some(Convergence { solver: Solver::Newton, iterations: iter, gradient_norm });
+            if options.penalty.is_penalized() {
+                result.suppress_inference();
+            }
+            return Ok(result);
+        }
+    }
+    return Err(RegressionError::NotConverged(options.max_iter));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::logreg::Penalty;
+    use ndarray::array;
+
+    #[test]
+    fn grouped_matches_rows_test() {
+        // Rows: x = 0 twice (one positive), x = 1 three times (two positive)
+        let rows_x = array![[1.0, 0.0], [1.0, 0.0], [1.0, 1.0], [1.0, 1.0], [1.0, 1.0]];
+        let rows_y = array![1.0, 0.0, 1.0, 1.0, 0.0];
+        let names = vec!["intercept".to_string(), "x".to_string()];
+        let options = SolverOptions::default();
+        let full = super::super::logreg::logistic_regression(&rows_x, &rows_y, &names, &options).unwrap();
+
+        let grouped_x = array![[1.0, 0.0], [1.0, 1.0]];
+        let grouped = grouped_regression(&grouped_x, &array![2.0, 3.0], &array![1.0, 2.0], &names, &options).unwrap();
+        for i in 0..2 {
+            assert!((full.coefficients[i] - grouped.coefficients[i]).abs() < 1e-8);
+            assert!((full.std_errors[i] - grouped.std_errors[i]).abs() < 1e-8);
+        }
+        assert!((full.log_likelihood - grouped.log_likelihood).abs() < 1e-8);
+
+        for penalty in [Penalty::Ridge(0.5), Penalty::Lasso(0.3)] {
+            let options = SolverOptions { penalty, ..SolverOptions::default() };
+            let full = super::super::logreg::logistic_regression(&rows_x, &rows_y, &names, &options).unwrap();
+            let grouped = grouped_regression(&grouped_x, &array![2.0, 3.0], &array![1.0, 2.0], &names, &options).unwrap();
+            for i in 0..2 {
+                assert!((full.coefficients[i] - grouped.coefficients[i]).abs() < 1e-6);
+            }
+            assert!(grouped.std_errors.iter().all(|se| se.is_nan()));
+        }
+    }
+}
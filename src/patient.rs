@@ -0,0 +1,349 @@
+// Patient records as stored on the chain. Dataset codes are parsed into typed fields when a record is
+// read, so a block can only hold values the dataset defines.
+
+use chrono::NaiveDate;
+use csv::StringRecord;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+use super::blockchain::{Payload, LEGACY_SCHEMA_VERSION};
+use super::model::{Feature, Outcome};
+use super::survival::{parse_date, SurvivalRecord, TimeOrigin, MISSING_DATE};
+
+//Number of columns in the dataset CSV
+pub const NUM_COLUMNS: usize = 23;
+
+//Header of the dataset CSV, also the field names of patients saved before fields were typed
+pub const COLUMN_NAMES: [&str; NUM_COLUMNS] = ["id", "sex", "patient_type", "entry_date", "date_symptoms", "date_died", "intubed",
+    "pneumonia", "age", "pregnancy", "diabetes", "copd", "asthma", "inmsupr", "hypertension", "other_disease", "cardiovascular",
+    "obesity", "renal_chronic", "tobacco", "contact_other_covid", "covid_res", "icu"];
+
+//Oldest age accepted, anything above is taken to be a data entry error
+pub const MAX_AGE: u32 = 130;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+//Enum describing why a record is not a valid patient
+pub enum PatientError {
+    MissingId,
+    InvalidField { field: &'static str, value: String },
+    Inconsistent(&'static str)
+}
+
+impl fmt::Display for PatientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            PatientError::MissingId => write!(f, "patient has no id"),
+            PatientError::InvalidField { field, value } => write!(f, "invalid {}: {:?}", field, value),
+            PatientError::Inconsistent(msg) => write!(f, "inconsistent record: {}", msg)
+        };
+    }
+}
+
+impl Error for PatientError {}
+
+fn invalid(field: &'static str, value: &str) -> PatientError {
+    return PatientError::InvalidField { field, value: value.to_string() };
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sex {
+    Female,
+    Male
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//Whether the patient was sent home or admitted
+pub enum PatientType {
+    Outpatient,
+    Inpatient
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CovidResult {
+    Positive,
+    Negative,
+    Pending
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+//Yes/no answer of the dataset, Unknown covers its not applicable (97), ignored (98) and unknown (99) codes
+pub enum Flag {
+    Yes,
+    No,
+    Unknown
+}
+
+impl Sex {
+    pub fn from_code(code: &str) -> Result<Sex, PatientError> {
+        return match code.trim() {
+            "1" => Ok(Sex::Female),
+            "2" => Ok(Sex::Male),
+            _ => Err(invalid("sex", code))
+        };
+    }
+}
+
+impl PatientType {
+    pub fn from_code(code: &str) -> Result<PatientType, PatientError> {
+        return match code.trim() {
+            "1" => Ok(PatientType::Outpatient),
+            "2" => Ok(PatientType::Inpatient),
+            _ => Err(invalid("patient_type", code))
+        };
+    }
+}
+
+impl CovidResult {
+    pub fn from_code(code: &str) -> Result<CovidResult, PatientError> {
+        return match code.trim() {
+            "1" => Ok(CovidResult::Positive),
+            "2" => Ok(CovidResult::Negative),
+            "3" => Ok(CovidResult::Pending),
+            _ => Err(invalid("covid_res", code))
+        };
+    }
+}
+
+impl Flag {
+    pub fn from_code(field: &'static str, code: &str) -> Result<Flag, PatientError> {
+        return match code.trim() {
+            "1" => Ok(Flag::Yes),
+            "2" => Ok(Flag::No),
+            "97" | "98" | "99" => Ok(Flag::Unknown),
+            _ => Err(invalid(field, code))
+        };
+    }
+
+    pub fn is_yes(&self) -> bool {
+        return *self == Flag::Yes;
+    }
+
+    //1 for yes, 0 for no, None when unknown
+    pub fn as_outcome(&self) -> Option<f64> {
+        return match self {
+            Flag::Yes => Some(1.0),
+            Flag::No => Some(0.0),
+            Flag::Unknown => None
+        };
+    }
+}
+
+//Structure of encapsulated patient data
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Patient {
+    pub id: String,
+    pub sex: Sex,
+    pub patient_type: PatientType,
+    pub entry_date: NaiveDate,
+    pub date_symptoms: NaiveDate,
+    //None for patients who did not die
+    pub date_died: Option<NaiveDate>,
+    pub intubed: Flag,
+    pub pneumonia: Flag,
+    pub age: u32,
+    pub pregnancy: Flag,
+    pub diabetes: Flag,
+    pub copd: Flag,
+    pub asthma: Flag,
+    pub inmsupr: Flag,
+    pub hypertension: Flag,
+    pub other_disease: Flag,
+    pub cardiovascular: Flag,
+    pub obesity: Flag,
+    pub renal_chronic: Flag,
+    pub tobacco: Flag,
+    pub contact_other_covid: Flag,
+    pub covid_res: CovidResult,
+    pub icu: Flag
+}
+
+impl Patient {
+    //Parses one row of the dataset CSV, columns in the order of the Kaggle export
+    pub fn from_record(record: &StringRecord) -> Result<Patient, PatientError> {
+        return Self::from_columns(&record.iter().collect::<Vec<&str>>());
+    }
+
+    //Parses the dataset columns of one patient and checks the result
+    pub fn from_columns(columns: &[&str]) -> Result<Patient, PatientError> {
+        let patient = Self::parse_columns(columns)?;
+        patient.validate()?;
+        return Ok(patient);
+    }
+
+    //Parses the dataset columns without the checks of validate, which are left to the caller
+    fn parse_columns(columns: &[&str]) -> Result<Patient, PatientError> {
+        if columns.len() < NUM_COLUMNS {
+            return Err(invalid("record", &format!("expected {} columns, found {}", NUM_COLUMNS, columns.len())));
+        }
+        let date = |field: &'static str, value: &str| parse_date(value).ok_or_else(|| invalid(field, value));
+        let date_died = match columns[5] {
+            MISSING_DATE => None,
+            value => Some(date("date_died", value)?)
+        };
+        return Ok(Patient {
+            id: columns[0].to_string(),
+            sex: Sex::from_code(columns[1])?,
+            patient_type: PatientType::from_code(columns[2])?,
+            entry_date: date("entry_date", columns[3])?,
+            date_symptoms: date("date_symptoms", columns[4])?,
+            date_died,
+            intubed: Flag::from_code("intubed", columns[6])?,
+            pneumonia: Flag::from_code("pneumonia", columns[7])?,
+            age: columns[8].trim().parse::<u32>().map_err(|_| invalid("age", columns[8]))?,
+            pregnancy: Flag::from_code("pregnancy", columns[9])?,
+            diabetes: Flag::from_code("diabetes", columns[10])?,
+            copd: Flag::from_code("copd", columns[11])?,
+            asthma: Flag::from_code("asthma", columns[12])?,
+            inmsupr: Flag::from_code("inmsupr", columns[13])?,
+            hypertension: Flag::from_code("hypertension", columns[14])?,
+            other_disease: Flag::from_code("other_disease", columns[15])?,
+            cardiovascular: Flag::from_code("cardiovascular", columns[16])?,
+            obesity: Flag::from_code("obesity", columns[17])?,
+            renal_chronic: Flag::from_code("renal_chronic", columns[18])?,
+            tobacco: Flag::from_code("tobacco", columns[19])?,
+            contact_other_covid: Flag::from_code("contact_other_covid", columns[20])?,
+            covid_res: CovidResult::from_code(columns[21])?,
+            icu: Flag::from_code("icu", columns[22])?
+        });
+    }
+
+    //Checks the rules the field types cannot express. Run before a record is mined and when a block is validated
+    pub fn validate(&self) -> Result<(), PatientError> {
+        if self.id.is_empty() {
+            return Err(PatientError::MissingId);
+        } else if self.age > MAX_AGE {
+            return Err(invalid("age", &self.age.to_string()));
+        } else if self.sex == Sex::Male && self.pregnancy.is_yes() {
+            return Err(PatientError::Inconsistent("male patient recorded as pregnant"));
+        } else if self.patient_type == PatientType::Outpatient && (self.icu.is_yes() || self.intubed.is_yes()) {
+            return Err(PatientError::Inconsistent("outpatient recorded in ICU or intubated"));
+        }
+        return Ok(());
+    }
+
+    pub fn died(&self) -> bool {
+        return self.date_died.is_some();
+    }
+
+    //Days from origin to death, censored at censor_date for survivors. None if the dates are out of order
+    pub fn survival_record(&self, origin: TimeOrigin, censor_date: NaiveDate) -> Option<SurvivalRecord> {
+        let start = match origin {
+            TimeOrigin::SymptomOnset => self.date_symptoms,
+            TimeOrigin::Admission => self.entry_date
+        };
+        let (end, event) = match self.date_died {
+            Some(date) => (date, true),
+            None => (censor_date, false)
+        };
+        let days = (end - start).num_days();
+        if days < 0 {
+            return None;
+        }
+        return Some(SurvivalRecord { time: days as f64, event });
+    }
+
+    //Whether the outcome happened, None where the dataset codes it as not applicable, ignored,
+    //unknown or, for COVID tests, still pending
+    pub fn outcome(&self, outcome: Outcome) -> Option<f64> {
+        return match outcome {
+            Outcome::Died => Some(if self.died() { 1.0 } else { 0.0 }),
+            Outcome::Icu => self.icu.as_outcome(),
+            Outcome::Intubated => self.intubed.as_outcome(),
+            Outcome::CovidPositive => match self.covid_res {
+                CovidResult::Positive => Some(1.0),
+                CovidResult::Negative => Some(0.0),
+                CovidResult::Pending => None
+            },
+            Outcome::Hospitalized => match self.patient_type {
+                PatientType::Outpatient => Some(0.0),
+                PatientType::Inpatient => Some(1.0)
+            }
+        };
+    }
+
+    //Value of a regression feature for this patient, clinical flags are 1 for yes and 0 for no or unknown
+    pub fn feature(&self, feature: Feature) -> f64 {
+        let flag = |flag: Flag| if flag.is_yes() { 1.0 } else { 0.0 };
+        return match feature {
+            Feature::Intercept => 1.0,
+            Feature::Male => if self.sex == Sex::Male { 1.0 } else { 0.0 },
+            Feature::Age => self.age as f64,
+            Feature::Pneumonia => flag(self.pneumonia),
+            Feature::Pregnancy => flag(self.pregnancy),
+            Feature::Diabetes => flag(self.diabetes),
+            Feature::Copd => flag(self.copd),
+            Feature::Asthma => flag(self.asthma),
+            Feature::Immunosuppressed => flag(self.inmsupr),
+            Feature::Hypertension => flag(self.hypertension),
+            Feature::OtherDisease => flag(self.other_disease),
+            Feature::Cardiovascular => flag(self.cardiovascular),
+            Feature::Obesity => flag(self.obesity),
+            Feature::RenalChronic => flag(self.renal_chronic),
+            Feature::Tobacco => flag(self.tobacco)
+        };
+    }
+}
+
+//Patients are indexed by their dataset id and checked with validate
+impl Payload for Patient {
+    type Error = PatientError;
+
+    fn id(&self) -> &str {
+        return &self.id;
+    }
+
+    fn check(&self) -> Result<(), PatientError> {
+        return self.validate();
+    }
+
+    //Patients saved before schema versions kept the dataset's codes, numbers and strings as they appear in the CSV,
+    //plus an if_died flag that date_died already carries
+    fn upgrade(version: u32, encoded: Value) -> Result<Patient, String> {
+        if version != LEGACY_SCHEMA_VERSION {
+            return Err(format!("no patient deserializer for schema version {}", version));
+        }
+        let columns = COLUMN_NAMES.iter()
+            .map(|field| match &encoded[*field] {
+                Value::String(value) => Ok(value.clone()),
+                Value::Number(value) => Ok(value.to_string()),
+                _ => Err(format!("legacy patient has no {}", field))
+            })
+            .collect::<Result<Vec<String>, String>>()?;
+        return Self::parse_columns(&columns.iter().map(String::as_str).collect::<Vec<&str>>()).map_err(|err| err.to_string());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ROW: [&str; NUM_COLUMNS] = ["16169f", "2", "2", "04-05-2020", "02-05-2020", "9999-99-99", "2", "1", "54",
+        "97", "2", "2", "2", "2", "1", "2", "2", "1", "2", "2", "99", "1", "2"];
+
+    #[test]
+    fn from_columns_test() {
+        let patient = Patient::from_columns(&ROW).unwrap();
+        assert_eq!(patient.sex, Sex::Male);
+        assert_eq!(patient.entry_date, NaiveDate::from_ymd_opt(2020, 5, 4).unwrap());
+        assert_eq!(patient.date_died, None);
+        assert_eq!(patient.pregnancy, Flag::Unknown);
+        assert_eq!(patient.feature(Feature::Pneumonia), 1.0);
+        assert_eq!(patient.feature(Feature::Pregnancy), 0.0);
+        assert_eq!(patient.outcome(Outcome::Hospitalized), Some(1.0));
+
+        let mut row = ROW;
+        row[1] = "3";
+        assert_eq!(Patient::from_columns(&row), Err(PatientError::InvalidField { field: "sex", value: "3".to_string() }));
+        row = ROW;
+        row[3] = "31-02-2020";
+        assert!(matches!(Patient::from_columns(&row), Err(PatientError::InvalidField { field: "entry_date", .. })));
+        row = ROW;
+        row[9] = "1";
+        assert!(matches!(Patient::from_columns(&row), Err(PatientError::Inconsistent(_))));
+        row = ROW;
+        row[0] = "";
+        assert_eq!(Patient::from_columns(&row), Err(PatientError::MissingId));
+    }
+}
@@ -0,0 +1,314 @@
+// Differentially private aggregate queries and model training against a privacy budget
+
+use ndarray::{Array1, Array2};
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+use std::fmt;
+
+use super::logreg::{fisher_information, invert, loss_gradient, RegressionError};
+
+// Logistic loss has second derivative at most 1/4, the constant c of objective perturbation
+const LOGISTIC_CURVATURE: f64 = 0.25;
+
+#[derive(Debug)]
+//Enum describing why a private query was refused
+pub enum PrivacyError {
+    BudgetExceeded { requested: f64, remaining: f64 },
+    InvalidParameter(&'static str),
+    Regression(RegressionError)
+}
+
+impl fmt::Display for PrivacyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            PrivacyError::BudgetExceeded { requested, remaining } =>
+                write!(f, "privacy budget exceeded: requested epsilon {} but only {} remains", requested, remaining),
+            PrivacyError::InvalidParameter(msg) => write!(f, "invalid privacy parameter: {}", msg),
+            PrivacyError::Regression(err) => write!(f, "{}", err)
+        };
+    }
+}
+
+impl Error for PrivacyError {}
+
+impl From<RegressionError> for PrivacyError {
+    fn from(err: RegressionError) -> Self {
+        return PrivacyError::Regression(err);
+    }
+}
+
+//One answered query and what it cost
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PrivacySpend {
+    pub label: String,
+    pub epsilon: f64,
+    pub delta: f64
+}
+
+//Total (epsilon, delta) allowed on a dataset, spent by sequential composition. Only the spending is
+//persisted, a reloaded budget draws fresh noise
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PrivacyBudget {
+    pub total_epsilon: f64,
+    pub total_delta: f64,
+    pub spent: Vec<PrivacySpend>,
+    #[serde(skip, default = "StdRng::from_entropy")]
+    rng: StdRng
+}
+
+impl PrivacyBudget {
+    pub fn new(total_epsilon: f64, total_delta: f64) -> Self {
+        return Self { total_epsilon, total_delta, spent: vec![], rng: StdRng::from_entropy() };
+    }
+
+    //Budget whose noise is reproducible, for tests and audits
+    pub fn with_seed(total_epsilon: f64, total_delta: f64, seed: u64) -> Self {
+        return Self { total_epsilon, total_delta, spent: vec![], rng: StdRng::seed_from_u64(seed) };
+    }
+
+    //Changes the totals, which may not be set below what has been spent already
+    pub fn set_totals(&mut self, total_epsilon: f64, total_delta: f64) -> Result<(), PrivacyError> {
+        if !is_positive(total_epsilon) || !(0.0..1.0).contains(&total_delta) {
+            return Err(PrivacyError::InvalidParameter("total epsilon must be positive and total delta in [0, 1)"));
+        }
+        if total_epsilon < self.spent_epsilon() || total_delta < self.spent_delta() {
+            return Err(PrivacyError::InvalidParameter("the budget cannot be set below what has been spent"));
+        }
+        self.total_epsilon = total_epsilon;
+        self.total_delta = total_delta;
+        return Ok(());
+    }
+
+    pub fn spent_epsilon(&self) -> f64 {
+        return self.spent.iter().map(|s| s.epsilon).sum();
+    }
+
+    pub fn spent_delta(&self) -> f64 {
+        return self.spent.iter().map(|s| s.delta).sum();
+    }
+
+    pub fn remaining_epsilon(&self) -> f64 {
+        return (self.total_epsilon - self.spent_epsilon()).max(0.0);
+    }
+
+    //Records the cost of a query, refusing it if the budget cannot cover it
+    fn spend(&mut self, label: &str, epsilon: f64, delta: f64) -> Result<(), PrivacyError> {
+        if !is_positive(epsilon) || !(0.0..1.0).contains(&delta) {
+            return Err(PrivacyError::InvalidParameter("epsilon must be positive and delta in [0, 1)"));
+        }
+        if epsilon > self.remaining_epsilon() + 1e-12 || self.spent_delta() + delta > self.total_delta + 1e-12 {
+            return Err(PrivacyError::BudgetExceeded { requested: epsilon, remaining: self.remaining_epsilon() });
+        }
+        self.spent.push(PrivacySpend { label: label.to_string(), epsilon, delta });
+        return Ok(());
+    }
+
+    //Sample from Laplace(0, scale) by inverting its CDF
+    fn laplace(&mut self, scale: f64) -> f64 {
+        let u: f64 = self.rng.gen_range(-0.5..0.5);
+        return -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln();
+    }
+
+    //Sample from N(0, sigma^2) with the Box-Muller transform
+    fn gaussian(&mut self, sigma: f64) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        return sigma * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+    }
+
+    //Count with Laplace noise, a count changes by at most one when one record changes
+    pub fn noisy_count(&mut self, label: &str, count: usize, epsilon: f64) -> Result<f64, PrivacyError> {
+        self.spend(label, epsilon, 0.0)?;
+        return Ok(count as f64 + self.laplace(1.0 / epsilon));
+    }
+
+    //Count with Gaussian noise calibrated for (epsilon, delta)
+    pub fn noisy_count_gaussian(&mut self, label: &str, count: usize, epsilon: f64, delta: f64) -> Result<f64, PrivacyError> {
+        if !is_positive(delta) {
+            return Err(PrivacyError::InvalidParameter("the gaussian mechanism needs a positive delta"));
+        }
+        self.spend(label, epsilon, delta)?;
+        let sigma = gaussian_sigma(1.0, epsilon, delta);
+        return Ok(count as f64 + self.gaussian(sigma));
+    }
+
+    //Mean of values clamped to [lower, upper] with Laplace noise. The number of records is treated as public,
+    //so replacing one record moves the mean by at most (upper - lower) / n
+    pub fn noisy_mean(&mut self, label: &str, values: &[f64], lower: f64, upper: f64, epsilon: f64) -> Result<f64, PrivacyError> {
+        let sensitivity = mean_sensitivity(values.len(), lower, upper)?;
+        self.spend(label, epsilon, 0.0)?;
+        return Ok(clamped_mean(values, lower, upper) + self.laplace(sensitivity / epsilon));
+    }
+
+    //Same as noisy_mean with Gaussian noise calibrated for (epsilon, delta)
+    pub fn noisy_mean_gaussian(&mut self, label: &str, values: &[f64], lower: f64, upper: f64, epsilon: f64, delta: f64) -> Result<f64, PrivacyError> {
+        let sensitivity = mean_sensitivity(values.len(), lower, upper)?;
+        if !is_positive(delta) {
+            return Err(PrivacyError::InvalidParameter("the gaussian mechanism needs a positive delta"));
+        }
+        self.spend(label, epsilon, delta)?;
+        let sigma = gaussian_sigma(sensitivity, epsilon, delta);
+        return Ok(clamped_mean(values, lower, upper) + self.gaussian(sigma));
+    }
+
+    //Epsilon-DP logistic regression by objective perturbation (Chaudhuri, Monteleoni and Sarwate, 2011).
+    //Rows are clipped to L2 norm row_norm_bound and rescaled into the unit ball, every coefficient including
+    //the intercept is ridge penalized with strength lambda on the mean loss, and a random linear term is added
+    //to the objective. Only the coefficients are released, inference statistics would need the raw rows
+    pub fn private_regression(&mut self, label: &str, x: &Array2<f64>, y: &Array1<f64>, epsilon: f64, lambda: f64,
+            row_norm_bound: f64) -> Result<Array1<f64>, PrivacyError> {
+        let (n_obs, n_features) = x.dim();
+        if n_obs == 0 || !is_positive(lambda) || !is_positive(row_norm_bound) {
+            return Err(PrivacyError::InvalidParameter("needs rows, a positive lambda and a positive row norm bound"));
+        }
+        let n = n_obs as f64;
+        let c = LOGISTIC_CURVATURE;
+        let mut epsilon_prime = epsilon - (1.0 + 2.0 * c / (n * lambda) + c * c / (n * n * lambda * lambda)).ln();
+        let mut extra_lambda = 0.0;
+        if epsilon_prime <= 0.0 {
+            extra_lambda = c / (n * ((epsilon / 4.0).exp() - 1.0)) - lambda;
+            epsilon_prime = epsilon / 2.0;
+        }
+        self.spend(label, epsilon, 0.0)?;
+
+        let noise = self.perturbation(n_features, epsilon_prime);
+
+        let mut x_scaled = x.clone();
+        for mut row in x_scaled.rows_mut() {
+            let row_norm = row.dot(&row).sqrt();
+            row /= row_norm.max(row_norm_bound);
+        }
+
+        // Newton on the summed objective: loss + n (lambda + extra) |w|^2 / 2 + b.w, strictly convex
+        let ridge = n * (lambda + extra_lambda);
+        let mut beta = Array1::<f64>::zeros(n_features);
+        for _iter in 0..100 {
+            let (_, gradient) = loss_gradient(&beta, &x_scaled, y);
+            let gradient = gradient + &beta * ridge + &noise;
+            let mut hessian = fisher_information(&beta, &x_scaled);
+            for j in 0..n_features {
+                hessian[[j, j]] += ridge;
+            }
+            let step = invert(&hessian).ok_or(RegressionError::SingularHessian)?.dot(&gradient);
+            beta -= &step;
+            if step.fold(0.0_f64, |m, d| m.max(d.abs())) < 1e-10 {
+                return Ok(beta / row_norm_bound);
+            }
+        }
+        return Err(PrivacyError::Regression(RegressionError::NotConverged(100)));
+    }
+
+    //Noise vector b with density proportional to exp(-epsilon' |b| / 2): Gamma(d, 2 / epsilon') norm, uniform direction
+    fn perturbation(&mut self, n_features: usize, epsilon_prime: f64) -> Array1<f64> {
+        let norm: f64 = (0..n_features).map(|_| -(1.0 - self.rng.gen::<f64>()).ln()).sum::<f64>() * 2.0 / epsilon_prime;
+        let direction = Array1::from_iter((0..n_features).map(|_| self.gaussian(1.0)));
+        let direction_norm = direction.dot(&direction).sqrt().max(f64::MIN_POSITIVE);
+        return direction * (norm / direction_norm);
+    }
+
+    //Lists every query charged to the budget
+    pub fn report(&self) -> String {
+        let mut out = format!("privacy budget: spent epsilon {:.4} of {:.4}, delta {:.2e} of {:.2e}\n",
+            self.spent_epsilon(), self.total_epsilon, self.spent_delta(), self.total_delta);
+        for spend in &self.spent {
+            out.push_str(&format!("  {:<32} epsilon {:.4}  delta {:.2e}\n", spend.label, spend.epsilon, spend.delta));
+        }
+        return out;
+    }
+}
+
+//False for zero, negative and NaN parameters
+fn is_positive(value: f64) -> bool {
+    return value > 0.0;
+}
+
+//Noise scale of the classical Gaussian mechanism
+fn gaussian_sigma(sensitivity: f64, epsilon: f64, delta: f64) -> f64 {
+    return (2.0 * (1.25 / delta).ln()).sqrt() * sensitivity / epsilon;
+}
+
+fn mean_sensitivity(n: usize, lower: f64, upper: f64) -> Result<f64, PrivacyError> {
+    if n == 0 || !is_positive(upper - lower) {
+        return Err(PrivacyError::InvalidParameter("a mean needs values and lower < upper"));
+    }
+    return Ok((upper - lower) / n as f64);
+}
+
+fn clamped_mean(values: &[f64], lower: f64, upper: f64) -> f64 {
+    return values.iter().map(|v| v.clamp(lower, upper)).sum::<f64>() / values.len() as f64;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn budget_test() {
+        let mut budget = PrivacyBudget::with_seed(1.0, 1e-6, 3);
+        assert!(budget.noisy_count("deaths", 100, 0.6).is_ok());
+        assert!(matches!(budget.noisy_count("deaths again", 100, 0.6), Err(PrivacyError::BudgetExceeded { .. })));
+        assert!((budget.spent_epsilon() - 0.6).abs() < 1e-12);
+        assert!(budget.noisy_mean("age", &[30.0, 40.0, 50.0], 0.0, 120.0, 0.4).is_ok());
+        assert_eq!(budget.spent.len(), 2);
+    }
+
+    #[test]
+    fn laplace_noise_test() {
+        let mut budget = PrivacyBudget::with_seed(1e9, 0.0, 11);
+        let samples: Vec<f64> = (0..20_000).map(|_| budget.laplace(2.0)).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let mean_abs = samples.iter().map(|s| s.abs()).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.1);
+        assert!((mean_abs - 2.0).abs() < 0.1);
+    }
+
+    //Sample standard deviation of the noise added to value
+    fn noise_sd(value: f64, draws: impl Iterator<Item = f64>) -> f64 {
+        let noise: Vec<f64> = draws.map(|draw| draw - value).collect();
+        return (noise.iter().map(|e| e * e).sum::<f64>() / noise.len() as f64).sqrt();
+    }
+
+    #[test]
+    fn noise_scale_test() {
+        let mut budget = PrivacyBudget::with_seed(1e9, 0.5, 13);
+        //Laplace(b) has standard deviation b * sqrt(2), with b = sensitivity / epsilon
+        let sd = noise_sd(7.0, (0..5_000).map(|_| budget.noisy_count("count", 7, 0.5).unwrap()));
+        assert!((sd / (2.0 * 2.0_f64.sqrt()) - 1.0).abs() < 0.08);
+        let ages = [20.0, 40.0, 60.0, 80.0];
+        let sd = noise_sd(50.0, (0..5_000).map(|_| budget.noisy_mean("age", &ages, 0.0, 120.0, 2.0).unwrap()));
+        assert!((sd / (30.0 / 2.0 * 2.0_f64.sqrt()) - 1.0).abs() < 0.08);
+        //Gaussian sigma = sqrt(2 ln(1.25 / delta)) * sensitivity / epsilon, about 4.84 for these
+        let sd = noise_sd(7.0, (0..5_000).map(|_| budget.noisy_count_gaussian("count", 7, 1.0, 1e-5).unwrap()));
+        assert!((sd / (2.0 * (1.25e5_f64).ln()).sqrt() - 1.0).abs() < 0.08);
+
+        //The perturbation's norm is Gamma(d, 2 / epsilon'), with mean 2 d / epsilon'
+        let norms: Vec<f64> = (0..5_000).map(|_| budget.perturbation(3, 0.5)).map(|b| b.dot(&b).sqrt()).collect();
+        assert!((norms.iter().sum::<f64>() / norms.len() as f64 / 12.0 - 1.0).abs() < 0.08);
+
+        assert!(budget.set_totals(1.0, 0.5).is_err());
+        assert!(budget.set_totals(2e9, 0.6).is_ok());
+        assert_eq!(budget.total_epsilon, 2e9);
+    }
+
+    #[test]
+    fn private_regression_test() {
+        let x = array![[1.0, 0.0], [1.0, 1.0], [1.0, 0.0], [1.0, 1.0]];
+        let y = array![0.0, 1.0, 0.0, 1.0];
+        let mut budget = PrivacyBudget::with_seed(2.0, 0.0, 5);
+        let beta = budget.private_regression("regression", &x, &y, 1.0, 0.1, 2.0_f64.sqrt()).unwrap();
+        assert_eq!(beta.len(), 2);
+        assert!((budget.remaining_epsilon() - 1.0).abs() < 1e-12);
+
+        //A third of the x = 0 rows and two thirds of the x = 1 rows are positive, so the maximum likelihood fit
+        //is -ln 2 and 2 ln 2. With a large epsilon and a tiny penalty the private fit is close to it
+        let x = Array2::from_shape_fn((60, 2), |(i, j)| if j == 0 || i >= 30 { 1.0 } else { 0.0 });
+        let y = Array1::from_shape_fn(60, |i| if (i < 30 && i % 3 == 0) || (i >= 30 && i % 3 != 0) { 1.0 } else { 0.0 });
+        let mut budget = PrivacyBudget::with_seed(1e4, 0.0, 5);
+        let beta = budget.private_regression("regression", &x, &y, 1e3, 1e-8, 2.0_f64.sqrt()).unwrap();
+        let ln2 = 2.0_f64.ln();
+        assert!((beta[0] + ln2).abs() < 0.02 && (beta[1] - 2.0 * ln2).abs() < 0.02, "{}", beta);
+    }
+}
@@ -0,0 +1,281 @@
+// Time-to-event analysis of the chain: Kaplan-Meier curves and Cox proportional hazards
+
+use chrono::NaiveDate;
+use ndarray::{Array1, Array2};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::logreg::{invert, line_search, two_sided_p_value, RegressionError, SolverOptions, Z_95};
+
+//Date formats seen in hospital exports, the Kaggle dataset uses the first one
+const DATE_FORMATS: [&str; 3] = ["%d-%m-%Y", "%Y-%m-%d", "%d/%m/%Y"];
+
+//Placeholder used by the dataset for a missing date, e.g. patients who did not die
+pub const MISSING_DATE: &str = "9999-99-99";
+
+//Parses a dataset date, None for the missing-date placeholder or anything unparseable
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    if value == MISSING_DATE {
+        return None;
+    }
+    return DATE_FORMATS.iter().find_map(|format| NaiveDate::parse_from_str(value, format).ok());
+}
+
+//Start of the clock for time-to-event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOrigin {
+    SymptomOnset,
+    Admission
+}
+
+//Days from the origin to death, or to the censoring date for patients still alive
+#[derive(Debug, Clone, PartialEq)]
+pub struct SurvivalRecord {
+    pub time: f64,
+    pub event: bool
+}
+
+//Survival estimate just after one distinct event time
+#[derive(Debug, Clone)]
+pub struct KaplanMeierPoint {
+    pub time: f64,
+    pub at_risk: usize,
+    pub events: usize,
+    pub censored: usize,
+    pub survival: f64,
+    pub std_err: f64
+}
+
+#[derive(Debug, Clone)]
+pub struct KaplanMeierCurve {
+    pub n: usize,
+    pub points: Vec<KaplanMeierPoint>
+}
+
+//Kaplan-Meier product-limit estimate with Greenwood standard errors
+pub fn kaplan_meier(records: &[SurvivalRecord]) -> KaplanMeierCurve {
+    let mut sorted: Vec<&SurvivalRecord> = records.iter().collect();
+    sorted.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut points = Vec::new();
+    let mut at_risk = sorted.len();
+    let mut survival = 1.0;
+    let mut greenwood = 0.0;
+    let mut i = 0;
+    while i < sorted.len() {
+        let time = sorted[i].time;
+        let mut events = 0;
+        let mut censored = 0;
+        while i < sorted.len() && sorted[i].time == time {
+            if sorted[i].event { events += 1; } else { censored += 1; }
+            i += 1;
+        }
+        if events > 0 {
+            survival *= 1.0 - events as f64 / at_risk as f64;
+            if at_risk > events {
+                greenwood += events as f64 / (at_risk as f64 * (at_risk - events) as f64);
+            }
+            points.push(KaplanMeierPoint { time, at_risk, events, censored, survival, std_err: survival * greenwood.sqrt() });
+        } else if let Some(last) = points.last_mut() {
+            last.censored += censored;
+        }
+        at_risk -= events + censored;
+    }
+    return KaplanMeierCurve { n: records.len(), points };
+}
+
+//One curve per group label
+pub fn kaplan_meier_by_group(records: &[(String, SurvivalRecord)]) -> BTreeMap<String, KaplanMeierCurve> {
+    let mut groups: BTreeMap<String, Vec<SurvivalRecord>> = BTreeMap::new();
+    for (group, record) in records {
+        groups.entry(group.clone()).or_default().push(record.clone());
+    }
+    return groups.iter().map(|(group, recs)| (group.clone(), kaplan_meier(recs))).collect();
+}
+
+impl KaplanMeierCurve {
+    //Estimated survival probability at time t
+    pub fn survival_at(&self, t: f64) -> f64 {
+        return self.points.iter().take_while(|p| p.time <= t).last().map_or(1.0, |p| p.survival);
+    }
+
+    //First time the survival estimate drops to one half or below, None if it never does
+    pub fn median(&self) -> Option<f64> {
+        return self.points.iter().find(|p| p.survival <= 0.5).map(|p| p.time);
+    }
+}
+
+impl fmt::Display for KaplanMeierCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>8} {:>8} {:>7} {:>9} {:>10} {:>9}", "time", "at risk", "events", "censored", "survival", "std err")?;
+        for p in &self.points {
+            writeln!(f, "{:>8.1} {:>8} {:>7} {:>9} {:>10.4} {:>9.4}", p.time, p.at_risk, p.events, p.censored, p.survival, p.std_err)?;
+        }
+        return Ok(());
+    }
+}
+
+//Fitted Cox proportional hazards model
+#[derive(Debug, Clone)]
+pub struct CoxResult {
+    pub names: Vec<String>,
+    pub coefficients: Array1<f64>,
+    pub std_errors: Array1<f64>,
+    pub p_values: Array1<f64>,
+    pub hazard_ratios: Array1<f64>,
+    pub hazard_lower: Array1<f64>,
+    pub hazard_upper: Array1<f64>,
+    pub log_partial_likelihood: f64,
+    pub n_obs: usize,
+    pub n_events: usize,
+    pub iterations: usize
+}
+
+//Breslow partial log-likelihood, its gradient and information matrix at beta
+fn cox_partial(beta: &Array1<f64>, x: &Array2<f64>, records: &[SurvivalRecord], order: &[usize]) -> (f64, Array1<f64>, Array2<f64>) {
+    let p = beta.len();
+    let risk = x.dot(beta);
+    let mut loglik = 0.0;
+    let mut gradient = Array1::<f64>::zeros(p);
+    let mut information = Array2::<f64>::zeros((p, p));
+    let mut s0 = 0.0;
+    let mut s1 = Array1::<f64>::zeros(p);
+    let mut s2 = Array2::<f64>::zeros((p, p));
+
+    // Walk times from the largest down so the sums always cover the risk set {time >= t}
+    let mut i = 0;
+    while i < order.len() {
+        let time = records[order[i]].time;
+        let start = i;
+        while i < order.len() && records[order[i]].time == time {
+            let idx = order[i];
+            let w = risk[idx].exp();
+            let row = x.row(idx);
+            s0 += w;
+            s1.scaled_add(w, &row);
+            for a in 0..p {
+                for b in 0..p {
+                    s2[[a, b]] += w * row[a] * row[b];
+                }
+            }
+            i += 1;
+        }
+        let events: Vec<usize> = order[start..i].iter().copied().filter(|idx| records[*idx].event).collect();
+        if events.is_empty() {
+            continue;
+        }
+        let d = events.len() as f64;
+        let mean = &s1 / s0;
+        for idx in &events {
+            loglik += risk[*idx];
+            gradient += &x.row(*idx);
+        }
+        loglik -= d * s0.ln();
+        gradient.scaled_add(-d, &mean);
+        for a in 0..p {
+            for b in 0..p {
+                information[[a, b]] += d * (s2[[a, b]] / s0 - mean[a] * mean[b]);
+            }
+        }
+    }
+    return (loglik, gradient, information);
+}
+
+//Cox proportional hazards by Newton-Raphson on the Breslow partial likelihood. x has no intercept column
+pub fn cox_regression(x: &Array2<f64>, records: &[SurvivalRecord], names: &[String], options: &SolverOptions)
+    -> Result<CoxResult, RegressionError> {
+    let (n_obs, n_features) = x.dim();
+    let n_events = records.iter().filter(|r| r.event).count();
+    if n_obs != records.len() || n_events == 0 || n_obs <= n_features {
+        return Err(RegressionError::TooFewRows);
+    }
+    let mut order: Vec<usize> = (0..n_obs).collect();
+    order.sort_by(|a, b| records[*b].time.total_cmp(&records[*a].time));
+
+    let mut beta = Array1::<f64>::zeros(n_features);
+    let (mut loglik, mut gradient, mut information) = cox_partial(&beta, x, records, &order);
+    for iter in 1..=options.max_iter {
+        let step = invert(&information).ok_or(RegressionError::SingularHessian)?.dot(&gradient);
+        //The partial likelihood is maximized, so the line search minimizes its negative
+        let (next_beta, next) = line_search(&beta, &step, -loglik, options.tol, |next_beta| {
+            let next = cox_partial(next_beta, x, records, &order);
+            return (-next.0, next);
+        })?;
+        let max_change = (&next_beta - &beta).fold(0.0_f64, |m, d| m.max(d.abs()));
+        beta = next_beta;
+        (loglik, gradient, information) = next;
+        if max_change < options.tol {
+            let std_errors = match invert(&information) {
+                Some(covariance) => covariance.diag().map(|v| v.max(0.0).sqrt()),
+                None => Array1::from_elem(n_features, f64::NAN)
+            };
+            let p_values = (&beta / &std_errors).map(|z| two_sided_p_value(*z));
+            return Ok(CoxResult {
+                names: names.to_vec(),
+                hazard_ratios: beta.map(|b| b.exp()),
+                hazard_lower: (&beta - &(&std_errors * Z_95)).map(|b| b.exp()),
+                hazard_upper: (&beta + &(&std_errors * Z_95)).map(|b| b.exp()),
+                coefficients: beta,
+                std_errors,
+                p_values,
+                log_partial_likelihood: loglik,
+                n_obs,
+                n_events,
+                iterations: iter
+            });
+        }
+    }
+    return Err(RegressionError::NotConverged(options.max_iter));
+}
+
+impl fmt::Display for CoxResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Cox proportional hazards, {} observations, {} events", self.n_obs, self.n_events)?;
+        writeln!(f, "Log partial likelihood: {:.4}, {} iterations\n", self.log_partial_likelihood, self.iterations)?;
+        writeln!(f, "{:<16} {:>10} {:>10} {:>9} {:>10} {:>10} {:>10}", "", "coef", "std err", "P>|z|", "hazard", "[0.025", "0.975]")?;
+        for i in 0..self.names.len() {
+            writeln!(f, "{:<16} {:>10.4} {:>10.4} {:>9.4} {:>10.4} {:>10.4} {:>10.4}", self.names[i], self.coefficients[i],
+                self.std_errors[i], self.p_values[i], self.hazard_ratios[i], self.hazard_lower[i], self.hazard_upper[i])?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ndarray::array;
+
+    fn record(time: f64, event: bool) -> SurvivalRecord {
+        return SurvivalRecord { time, event };
+    }
+
+    #[test]
+    fn parse_date_test() {
+        assert_eq!(parse_date("04-05-2020"), NaiveDate::from_ymd_opt(2020, 5, 4));
+        assert_eq!(parse_date("2020-05-04"), NaiveDate::from_ymd_opt(2020, 5, 4));
+        assert_eq!(parse_date(MISSING_DATE), None);
+    }
+
+    #[test]
+    fn kaplan_meier_test() {
+        let records = vec![record(1.0, true), record(2.0, false), record(3.0, true), record(4.0, true), record(5.0, false)];
+        let curve = kaplan_meier(&records);
+        assert_eq!(curve.points.len(), 3);
+        assert!((curve.survival_at(1.0) - 0.8).abs() < 1e-12);
+        assert!((curve.survival_at(3.0) - 0.8 * 2.0 / 3.0).abs() < 1e-12);
+        assert!((curve.survival_at(4.0) - 0.8 * 2.0 / 3.0 * 0.5).abs() < 1e-12);
+        assert_eq!(curve.median(), Some(4.0));
+    }
+
+    #[test]
+    fn cox_test() {
+        // Exposed patients die earlier, so their hazard ratio must be above one
+        let x = array![[1.0], [1.0], [1.0], [0.0], [0.0], [0.0], [1.0], [0.0]];
+        let records = vec![record(1.0, true), record(2.0, true), record(4.0, false), record(3.0, true),
+            record(5.0, true), record(6.0, false), record(2.5, true), record(7.0, true)];
+        let result = cox_regression(&x, &records, &["exposed".to_string()], &SolverOptions::default()).unwrap();
+        assert!(result.hazard_ratios[0] > 1.0);
+        assert_eq!(result.n_events, 6);
+    }
+}